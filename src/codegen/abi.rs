@@ -0,0 +1,560 @@
+use super::{CompileError, CompileResult};
+use crate::{CodegenType, Compiler, FunctionType, Span};
+use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    module::Linkage,
+    types::{AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum},
+    values::{BasicMetadataValueEnum, FunctionValue, PointerValue},
+    AddressSpace,
+};
+
+// How an aggregate crosses the boundary to C code under the calling convention of the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PassMode<'a> {
+    Direct,
+    // Passed in registers, reinterpreted through memory as the given type.
+    Cast(BasicTypeEnum<'a>),
+    // Passed as a pointer to a copy. `byval` copies move to the stack of the callee, as on
+    // x86-64 System V. Returned values are written through a hidden `sret` pointer instead.
+    Indirect { byval: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Convention {
+    SystemV,
+    Win64,
+    Aapcs64,
+    // Aggregates are handed to LLVM as they are.
+    Other,
+}
+
+fn convention(compiler: &Compiler) -> Convention {
+    let triple = compiler.module.get_triple();
+    let triple = triple.as_str().to_string_lossy();
+
+    if triple.starts_with("x86_64") {
+        if triple.contains("windows") {
+            Convention::Win64
+        } else {
+            Convention::SystemV
+        }
+    } else if triple.starts_with("aarch64") || triple.starts_with("arm64") {
+        Convention::Aapcs64
+    } else {
+        Convention::Other
+    }
+}
+
+// Collects the scalars of `ty` along with their offsets from the start of the outermost type.
+fn flatten<'a>(
+    compiler: &Compiler<'a>,
+    ty: BasicTypeEnum<'a>,
+    offset: u64,
+    scalars: &mut Vec<(u64, BasicTypeEnum<'a>)>,
+) {
+    match ty {
+        BasicTypeEnum::StructType(struct_type) => {
+            for (i, field) in struct_type.get_field_types().into_iter().enumerate() {
+                let field_offset = compiler
+                    .target_data
+                    .offset_of_element(&struct_type, i as u32)
+                    .unwrap();
+                flatten(compiler, field, offset + field_offset, scalars);
+            }
+        }
+        BasicTypeEnum::ArrayType(array_type) => {
+            let element = array_type.get_element_type();
+            let size = compiler.target_data.get_abi_size(&element);
+            for i in 0..array_type.len() as u64 {
+                flatten(compiler, element, offset + i * size, scalars);
+            }
+        }
+        ty => scalars.push((offset, ty)),
+    }
+}
+
+// The type of the eightbyte at `start`: SSE if it only holds floats, INTEGER otherwise.
+fn eightbyte<'a>(
+    compiler: &Compiler<'a>,
+    scalars: &[(u64, BasicTypeEnum<'a>)],
+    start: u64,
+    size: u64,
+) -> BasicTypeEnum<'a> {
+    let fields = scalars
+        .iter()
+        .filter(|(offset, _)| (start..start + 8).contains(offset))
+        .map(|(_, ty)| *ty)
+        .collect::<Vec<_>>();
+
+    if !fields.is_empty() && fields.iter().all(|ty| ty.is_float_type()) {
+        match fields.as_slice() {
+            [ty] => *ty,
+            _ => compiler.context.f32_type().vec_type(2).into(),
+        }
+    } else {
+        let bytes = (size - start).min(8);
+        compiler
+            .context
+            .custom_width_int_type(bytes as u32 * 8)
+            .into()
+    }
+}
+
+// A homogeneous floating-point aggregate: up to four floats of the same type.
+fn homogeneous_float<'a>(scalars: &[(u64, BasicTypeEnum<'a>)]) -> Option<BasicTypeEnum<'a>> {
+    let (_, first) = *scalars.first()?;
+
+    if first.is_float_type() && scalars.len() <= 4 && scalars.iter().all(|(_, ty)| *ty == first) {
+        Some(
+            first
+                .into_float_type()
+                .array_type(scalars.len() as u32)
+                .into(),
+        )
+    } else {
+        None
+    }
+}
+
+// The argument registers of x86-64 System V left free by the preceding arguments.
+#[derive(Debug, Clone, Copy)]
+struct Registers {
+    integer: usize,
+    sse: usize,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self { integer: 6, sse: 8 }
+    }
+}
+
+impl Registers {
+    // Takes the registers if they are all free. Otherwise the argument goes on the stack.
+    fn take(&mut self, integer: usize, sse: usize) -> bool {
+        if integer <= self.integer && sse <= self.sse {
+            self.integer -= integer;
+            self.sse -= sse;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn classify<'a>(
+    compiler: &Compiler<'a>,
+    ty: BasicTypeEnum<'a>,
+    is_return: bool,
+    registers: &mut Registers,
+) -> PassMode<'a> {
+    if !ty.is_struct_type() {
+        if !is_return {
+            if ty.is_float_type() {
+                registers.take(0, 1);
+            } else {
+                registers.take(1, 0);
+            }
+        }
+        return PassMode::Direct;
+    }
+
+    let size = compiler.target_data.get_abi_size(&ty);
+    if size == 0 {
+        return PassMode::Direct;
+    }
+
+    let mut scalars = Vec::new();
+    flatten(compiler, ty, 0, &mut scalars);
+
+    match convention(compiler) {
+        // The hidden `sret` pointer takes the first integer register.
+        Convention::SystemV if size > 16 && is_return => {
+            registers.take(1, 0);
+            PassMode::Indirect { byval: false }
+        }
+        Convention::SystemV if size > 16 => PassMode::Indirect { byval: true },
+        Convention::SystemV => {
+            let eightbytes = (0..size)
+                .step_by(8)
+                .map(|start| eightbyte(compiler, &scalars, start, size))
+                .collect::<Vec<_>>();
+
+            // An argument is only split across registers if all of its eightbytes fit.
+            let sse = eightbytes.iter().filter(|ty| !ty.is_int_type()).count();
+            if !is_return && !registers.take(eightbytes.len() - sse, sse) {
+                return PassMode::Indirect { byval: true };
+            }
+
+            PassMode::Cast(match eightbytes.as_slice() {
+                [ty] => *ty,
+                _ => compiler.context.struct_type(&eightbytes, false).into(),
+            })
+        }
+        Convention::Win64 if matches!(size, 1 | 2 | 4 | 8) => PassMode::Cast(
+            compiler
+                .context
+                .custom_width_int_type(size as u32 * 8)
+                .into(),
+        ),
+        Convention::Win64 => PassMode::Indirect { byval: false },
+        Convention::Aapcs64 => match homogeneous_float(&scalars) {
+            Some(ty) => PassMode::Cast(ty),
+            None if size > 16 => PassMode::Indirect { byval: false },
+            None if size > 8 => PassMode::Cast(compiler.context.i64_type().array_type(2).into()),
+            None => PassMode::Cast(compiler.context.i64_type().into()),
+        },
+        Convention::Other => PassMode::Direct,
+    }
+}
+
+// A slot large and aligned enough to hold a value of either type.
+fn build_slot<'a>(
+    compiler: &Compiler<'a>,
+    ty: BasicTypeEnum<'a>,
+    cast: BasicTypeEnum<'a>,
+) -> PointerValue<'a> {
+    let target_data = &compiler.target_data;
+    let slot_type = if target_data.get_abi_size(&cast) > target_data.get_abi_size(&ty) {
+        cast
+    } else {
+        ty
+    };
+    let alignment = target_data
+        .get_abi_alignment(&ty)
+        .max(target_data.get_abi_alignment(&cast));
+
+    let slot = compiler.builder.build_alloca(slot_type, "abi.slot");
+    slot.as_instruction()
+        .unwrap()
+        .set_alignment(alignment)
+        .unwrap();
+    slot
+}
+
+fn type_attribute<'a>(compiler: &Compiler<'a>, name: &str, ty: BasicTypeEnum<'a>) -> Attribute {
+    compiler.context.create_type_attribute(
+        Attribute::get_named_enum_kind_id(name),
+        ty.as_any_type_enum(),
+    )
+}
+
+// Declares the external function `name`, taking and returning aggregates as C code does. A `str`
+// returned by C code is a C string without the header of a Swua string, so it is copied into a new
+// string. When the declaration differs from the one of a Swua function, it is called through a
+// private wrapper with the Swua signature, which is returned instead so that the function can still
+// be called indirectly.
+pub fn declare_external_function<'a>(
+    compiler: &Compiler<'a>,
+    name: &str,
+    function_type: &FunctionType,
+    span: Span,
+) -> CompileResult<FunctionValue<'a>> {
+    let llvm_function_type = function_type.to_llvm_function_type(compiler.context);
+    let parameter_types = llvm_function_type.get_param_types();
+    let return_type = function_type.return_type.to_llvm_type(compiler.context);
+
+    let mut registers = Registers::default();
+    let return_mode = classify(compiler, return_type, true, &mut registers);
+    let parameter_modes = parameter_types
+        .iter()
+        .map(|ty| classify(compiler, *ty, false, &mut registers))
+        .collect::<Vec<_>>();
+
+    let copies_string = *function_type.return_type == CodegenType::String;
+    if !copies_string && function_type.return_type.holds_references() {
+        return Err(CompileError::unsupported_return_type(
+            &function_type.return_type,
+            span,
+        ));
+    }
+
+    if return_mode == PassMode::Direct
+        && parameter_modes.iter().all(|mode| *mode == PassMode::Direct)
+        && !copies_string
+    {
+        return Ok(compiler.module.add_function(name, llvm_function_type, None));
+    }
+
+    // The variadic arguments could not be forwarded by the wrapper.
+    if function_type.variadic && copies_string {
+        return Err(CompileError::string_returned_by_variadic_function(
+            name, span,
+        ));
+    }
+    if function_type.variadic {
+        return Err(CompileError::struct_by_value_in_variadic_function(
+            name, span,
+        ));
+    }
+
+    let pointer_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+    let mut lowered_parameters: Vec<BasicMetadataTypeEnum> = Vec::new();
+
+    if let PassMode::Indirect { .. } = return_mode {
+        lowered_parameters.push(pointer_type.into());
+    }
+    for (ty, mode) in parameter_types.iter().zip(parameter_modes.iter()) {
+        lowered_parameters.push(match mode {
+            PassMode::Direct => (*ty).into(),
+            PassMode::Cast(cast) => (*cast).into(),
+            PassMode::Indirect { .. } => pointer_type.into(),
+        });
+    }
+
+    let lowered_type = match return_mode {
+        PassMode::Direct => return_type.fn_type(&lowered_parameters, false),
+        PassMode::Cast(cast) => cast.fn_type(&lowered_parameters, false),
+        PassMode::Indirect { .. } => compiler
+            .context
+            .void_type()
+            .fn_type(&lowered_parameters, false),
+    };
+    let external = compiler.module.add_function(name, lowered_type, None);
+
+    let offset = match return_mode {
+        PassMode::Indirect { .. } => {
+            let sret = type_attribute(compiler, "sret", return_type);
+            external.add_attribute(AttributeLoc::Param(0), sret);
+            1
+        }
+        _ => 0,
+    };
+    for (i, (ty, mode)) in parameter_types
+        .iter()
+        .zip(parameter_modes.iter())
+        .enumerate()
+    {
+        if let PassMode::Indirect { byval: true } = mode {
+            let byval = type_attribute(compiler, "byval", *ty);
+            external.add_attribute(AttributeLoc::Param(i as u32 + offset), byval);
+        }
+    }
+
+    let wrapper = compiler.module.add_function(
+        format!("{name}.abi").as_str(),
+        llvm_function_type,
+        Some(Linkage::Private),
+    );
+
+    let insert_block = compiler.builder.get_insert_block();
+    compiler
+        .builder
+        .position_at_end(compiler.context.append_basic_block(wrapper, "entry"));
+
+    let mut arguments: Vec<BasicMetadataValueEnum> = Vec::new();
+
+    let result = match return_mode {
+        PassMode::Indirect { .. } => {
+            let slot = compiler.builder.build_alloca(return_type, "abi.sret");
+            arguments.push(slot.into());
+            Some(slot)
+        }
+        _ => None,
+    };
+    for (i, (ty, mode)) in parameter_types
+        .iter()
+        .zip(parameter_modes.iter())
+        .enumerate()
+    {
+        let parameter = wrapper.get_nth_param(i as u32).unwrap();
+
+        arguments.push(match mode {
+            PassMode::Direct => parameter.into(),
+            PassMode::Cast(cast) => {
+                let slot = build_slot(compiler, *ty, *cast);
+                compiler.builder.build_store(slot, parameter);
+                compiler.builder.build_load(*cast, slot, "abi.arg").into()
+            }
+            PassMode::Indirect { .. } => {
+                let slot = compiler.builder.build_alloca(*ty, "abi.arg");
+                compiler.builder.build_store(slot, parameter);
+                slot.into()
+            }
+        });
+    }
+
+    let call = compiler
+        .builder
+        .build_call(external, arguments.as_slice(), "abi.call");
+    for (i, (ty, mode)) in parameter_types
+        .iter()
+        .zip(parameter_modes.iter())
+        .enumerate()
+    {
+        if let PassMode::Indirect { byval: true } = mode {
+            let byval = type_attribute(compiler, "byval", *ty);
+            call.add_attribute(AttributeLoc::Param(i as u32 + offset), byval);
+        }
+    }
+
+    let value = match (return_mode, result) {
+        (PassMode::Indirect { .. }, Some(slot)) => {
+            let sret = type_attribute(compiler, "sret", return_type);
+            call.add_attribute(AttributeLoc::Param(0), sret);
+            compiler.builder.build_load(return_type, slot, "abi.ret")
+        }
+        (PassMode::Cast(cast), _) => {
+            let slot = build_slot(compiler, return_type, cast);
+            let value = call.try_as_basic_value().left().unwrap();
+            compiler.builder.build_store(slot, value);
+            compiler.builder.build_load(return_type, slot, "abi.ret")
+        }
+        _ if copies_string => {
+            let str_from_cstr = compiler.get_or_declare_function(
+                "str_from_cstr",
+                pointer_type.fn_type(&[pointer_type.into()], false),
+            );
+            let string = call.try_as_basic_value().left().unwrap();
+            compiler
+                .builder
+                .build_call(str_from_cstr, &[string.into()], "abi.str")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+        }
+        _ => call.try_as_basic_value().left().unwrap(),
+    };
+    compiler.builder.build_return(Some(&value));
+
+    if let Some(block) = insert_block {
+        compiler.builder.position_at_end(block);
+    }
+
+    Ok(wrapper)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{
+        testing::{calls, compile, compile_error, function},
+        CompileErrorKind,
+    };
+    use inkwell::{
+        attributes::{Attribute, AttributeLoc},
+        context::Context,
+        values::FunctionValue,
+    };
+
+    const PAIR: &str = "struct Pair\n    | first int\n    | second int\n\n";
+
+    fn byval_parameters(function: FunctionValue) -> Vec<u32> {
+        let byval = Attribute::get_named_enum_kind_id("byval");
+
+        (0..function.count_params())
+            .filter(|i| {
+                function
+                    .get_enum_attribute(AttributeLoc::Param(*i), byval)
+                    .is_some()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_struct_in_registers() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "struct Vector\n    | x f32\n    | y f32\n\nextern scale(Vector, f32) -> Vector",
+        );
+
+        let scale = function(&module, "scale");
+        let vector = context.f32_type().vec_type(2);
+        assert_eq!(scale.get_type().get_return_type(), Some(vector.into()));
+        assert_eq!(
+            scale.get_type().get_param_types(),
+            vec![vector.into(), context.f32_type().into()]
+        );
+        function(&module, "scale.abi");
+    }
+
+    #[test]
+    fn large_struct_in_memory() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "struct Triple\n    | a int\n    | b int\n    | c int\n\nextern flip(Triple) -> Triple",
+        );
+
+        let flip = function(&module, "flip");
+        let sret = Attribute::get_named_enum_kind_id("sret");
+        assert_eq!(flip.get_type().get_return_type(), None);
+        assert!(flip
+            .get_enum_attribute(AttributeLoc::Param(0), sret)
+            .is_some());
+        assert_eq!(byval_parameters(flip), vec![1]);
+    }
+
+    #[test]
+    fn struct_split_across_remaining_registers() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            &format!("{PAIR}extern f(int, int, int, int, Pair) -> int"),
+        );
+
+        let f = function(&module, "f");
+        let pair = context.struct_type(
+            &[context.i64_type().into(), context.i64_type().into()],
+            false,
+        );
+        assert_eq!(f.get_type().get_param_types()[4], pair.into());
+        assert!(byval_parameters(f).is_empty());
+    }
+
+    #[test]
+    fn struct_on_stack_when_registers_run_out() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            &format!("{PAIR}extern f(int, int, int, int, int, Pair) -> int"),
+        );
+        assert_eq!(byval_parameters(function(&module, "f")), vec![5]);
+
+        // The `sret` pointer takes one of the integer registers.
+        let module = compile(
+            &context,
+            &format!("{PAIR}struct Triple\n    | a int\n    | b int\n    | c int\n\nextern g(int, int, int, int, Pair) -> Triple"),
+        );
+        assert_eq!(byval_parameters(function(&module, "g")), vec![5]);
+
+        // Floats go in SSE registers, which are still free.
+        let module = compile(
+            &context,
+            "struct Point\n    | x float\n    | y float\n\nextern h(int, int, int, int, int, int, Point) -> int",
+        );
+        assert!(byval_parameters(function(&module, "h")).is_empty());
+    }
+
+    #[test]
+    fn struct_in_variadic_function() {
+        assert!(matches!(
+            compile_error(&format!("{PAIR}extern f(Pair, ...) -> int")),
+            CompileErrorKind::StructByValueInVariadicFunction(_)
+        ));
+    }
+
+    #[test]
+    fn external_string_return() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "extern getenv(str) -> str\n\ndefine home -> str = getenv(\"HOME\")",
+        );
+
+        // The C string is copied by the wrapper the function is called through.
+        assert_eq!(calls(function(&module, "home"), "getenv.abi").len(), 1);
+        let wrapper = function(&module, "getenv.abi");
+        assert_eq!(calls(wrapper, "getenv").len(), 1);
+        assert_eq!(calls(wrapper, "str_from_cstr").len(), 1);
+
+        assert!(matches!(
+            compile_error("extern format(str, ...) -> str"),
+            CompileErrorKind::StringReturnedByVariadicFunction(_)
+        ));
+        assert!(matches!(
+            compile_error("extern pair(int) -> (str, int)"),
+            CompileErrorKind::UnsupportedReturnType(_)
+        ));
+    }
+}
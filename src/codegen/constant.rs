@@ -0,0 +1,319 @@
+use super::{
+    symbol_table::SymbolTable,
+    types::{CodegenType, IntegerType},
+    CompileError, CompileResult, Expression, Literal,
+};
+use crate::{BinaryOperator, Compiler, Span, UnaryOperator, Value};
+use inkwell::values::BasicValue;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    // Integers of other widths, kept sign or zero extended to 64 bits.
+    Integer(i64, IntegerType),
+    Float(f64),
+    F32(f32),
+    Boolean(bool),
+    String(String),
+}
+
+impl Constant {
+    pub fn ty(&self) -> CodegenType {
+        match self {
+            Constant::Int(_) => CodegenType::Int,
+            Constant::Integer(_, ty) => CodegenType::Integer(*ty),
+            Constant::Float(_) => CodegenType::Float,
+            Constant::F32(_) => CodegenType::F32,
+            Constant::Boolean(_) => CodegenType::Boolean,
+            Constant::String(_) => CodegenType::String,
+        }
+    }
+
+    pub fn codegen<'a>(&self, compiler: &Compiler<'a>) -> Value<'a> {
+        let value = match self {
+            Constant::Int(value) => compiler
+                .context
+                .i64_type()
+                .const_int(*value as u64, false)
+                .as_basic_value_enum(),
+            Constant::Integer(value, ty) => compiler
+                .context
+                .custom_width_int_type(ty.bits)
+                .const_int(*value as u64, ty.signed)
+                .as_basic_value_enum(),
+            Constant::Float(value) => compiler
+                .context
+                .f64_type()
+                .const_float(*value)
+                .as_basic_value_enum(),
+            Constant::F32(value) => compiler
+                .context
+                .f32_type()
+                .const_float(*value as f64)
+                .as_basic_value_enum(),
+            Constant::Boolean(value) => compiler
+                .context
+                .bool_type()
+                .const_int(*value as u64, false)
+                .as_basic_value_enum(),
+            Constant::String(value) => compiler.build_string(value).as_basic_value_enum(),
+        };
+
+        Value::new(value, self.ty())
+    }
+}
+
+pub fn evaluate(expression: &Expression, symbol_table: &SymbolTable) -> CompileResult<Constant> {
+    let span = Span::from(expression.clone());
+
+    Ok(match expression {
+        Expression::Literal(literal) => match literal {
+            Literal::Int(int) => match int.ty() {
+                CodegenType::Integer(ty) => Constant::Integer(int.value, ty),
+                _ => Constant::Int(int.value),
+            },
+            Literal::Float(float) if float.single_precision => Constant::F32(float.value as f32),
+            Literal::Float(float) => Constant::Float(float.value),
+            Literal::Boolean(boolean) => Constant::Boolean(boolean.value),
+            Literal::String(string) => Constant::String(string.value.clone()),
+            Literal::Identifier(identifier) => {
+                match symbol_table.get_constant(&identifier.identifier) {
+                    Some(entry) => entry.value,
+                    None => return Err(CompileError::not_constant(span)),
+                }
+            }
+            _ => return Err(CompileError::not_constant(span)),
+        },
+        Expression::Unary(unary) => {
+            match (&unary.operator, evaluate(&unary.expression, symbol_table)?) {
+                (UnaryOperator::Minus, Constant::Int(value)) => Constant::Int(value.wrapping_neg()),
+                (UnaryOperator::Minus, Constant::Integer(value, ty)) => {
+                    Constant::Integer(truncate(value.wrapping_neg(), ty), ty)
+                }
+                (UnaryOperator::Minus, Constant::Float(value)) => Constant::Float(-value),
+                (UnaryOperator::Minus, Constant::F32(value)) => Constant::F32(-value),
+                (UnaryOperator::Not, Constant::Boolean(value)) => Constant::Boolean(!value),
+                (UnaryOperator::BitNot, Constant::Int(value)) => Constant::Int(!value),
+                (UnaryOperator::BitNot, Constant::Integer(value, ty)) => {
+                    Constant::Integer(truncate(!value, ty), ty)
+                }
+                (UnaryOperator::Minus, _) => {
+                    return Err(CompileError::expected("int or float", span))
+                }
+                (UnaryOperator::Not, _) => return Err(CompileError::expected("boolean", span)),
+                (UnaryOperator::BitNot, _) => return Err(CompileError::expected("int", span)),
+            }
+        }
+        Expression::Binary(binary) => {
+            let left = evaluate(&binary.left, symbol_table)?;
+            let right = evaluate(&binary.right, symbol_table)?;
+
+            evaluate_binary(&binary.operator, left, right, span)?
+        }
+        Expression::Cast(cast) => {
+            let value = evaluate(&cast.expression, symbol_table)?;
+            let ty = cast.cast_ty.kind.to_codegen_type(symbol_table)?;
+
+            match (ty, value) {
+                (CodegenType::Int, Constant::Int(value) | Constant::Integer(value, _)) => {
+                    Constant::Int(value)
+                }
+                (CodegenType::Integer(ty), Constant::Int(value) | Constant::Integer(value, _)) => {
+                    Constant::Integer(truncate(value, ty), ty)
+                }
+                (CodegenType::Int, Constant::Float(value)) => Constant::Int(value as i64),
+                (CodegenType::Int, Constant::Boolean(value)) => Constant::Int(value as i64),
+                (CodegenType::Float, Constant::Int(value)) => Constant::Float(value as f64),
+                (CodegenType::Float, Constant::Float(value)) => Constant::Float(value),
+                _ => return Err(CompileError::not_constant(span)),
+            }
+        }
+        Expression::Ternary(ternary) => match evaluate(&ternary.condition, symbol_table)? {
+            Constant::Boolean(condition) => {
+                let consequence = evaluate(&ternary.consequence, symbol_table)?;
+                let alternative = evaluate(&ternary.alternative, symbol_table)?;
+
+                if consequence.ty() != alternative.ty() {
+                    return Err(CompileError::type_mismatch(
+                        consequence.ty(),
+                        alternative.ty(),
+                        span,
+                    ));
+                }
+
+                if condition {
+                    consequence
+                } else {
+                    alternative
+                }
+            }
+            _ => return Err(CompileError::expected("boolean", span)),
+        },
+        _ => return Err(CompileError::not_constant(span)),
+    })
+}
+
+macro_rules! evaluate_float {
+    ($operator:expr, $left:expr, $right:expr, $constant:path, $span:expr) => {
+        match $operator {
+            Plus => $constant($left + $right),
+            Minus => $constant($left - $right),
+            Asterisk => $constant($left * $right),
+            Slash => $constant($left / $right),
+            Percent => $constant($left % $right),
+            EQ => Constant::Boolean($left == $right),
+            NEQ => Constant::Boolean($left != $right),
+            LT => Constant::Boolean($left < $right),
+            GT => Constant::Boolean($left > $right),
+            LTE => Constant::Boolean($left <= $right),
+            GTE => Constant::Boolean($left >= $right),
+            _ => return Err(CompileError::expected("int", $span)),
+        }
+    };
+}
+
+fn evaluate_binary(
+    operator: &BinaryOperator,
+    left: Constant,
+    right: Constant,
+    span: Span,
+) -> CompileResult<Constant> {
+    use BinaryOperator::*;
+
+    Ok(match (left, right) {
+        (Constant::Int(left), Constant::Int(right)) => match operator {
+            Plus => Constant::Int(left.wrapping_add(right)),
+            Minus => Constant::Int(left.wrapping_sub(right)),
+            Asterisk => Constant::Int(left.wrapping_mul(right)),
+            Slash | Percent if right == 0 => return Err(CompileError::division_by_zero(span)),
+            Slash => Constant::Int(left.wrapping_div(right)),
+            Percent => Constant::Int(left.wrapping_rem(right)),
+            EQ => Constant::Boolean(left == right),
+            NEQ => Constant::Boolean(left != right),
+            LT => Constant::Boolean(left < right),
+            GT => Constant::Boolean(left > right),
+            LTE => Constant::Boolean(left <= right),
+            GTE => Constant::Boolean(left >= right),
+            BitAnd => Constant::Int(left & right),
+            BitOr => Constant::Int(left | right),
+            BitXor => Constant::Int(left ^ right),
+            Shl | Shr if !(0..64).contains(&right) => {
+                return Err(CompileError::shift_out_of_range(right, span))
+            }
+            Shl => Constant::Int(left << right),
+            Shr => Constant::Int(left >> right),
+            _ => return Err(CompileError::expected("boolean", span)),
+        },
+        (Constant::Integer(left, ty), Constant::Integer(right, right_ty)) if ty == right_ty => {
+            evaluate_integer(operator, left, right, ty, span)?
+        }
+        (Constant::Float(left), Constant::Float(right)) => {
+            evaluate_float!(operator, left, right, Constant::Float, span)
+        }
+        (Constant::F32(left), Constant::F32(right)) => {
+            evaluate_float!(operator, left, right, Constant::F32, span)
+        }
+        (Constant::Boolean(left), Constant::Boolean(right)) => match operator {
+            And => Constant::Boolean(left && right),
+            Or => Constant::Boolean(left || right),
+            _ => return Err(CompileError::expected("int or float", span)),
+        },
+        (left, right) if left.ty() != right.ty() => {
+            return Err(CompileError::type_mismatch(left.ty(), right.ty(), span))
+        }
+        _ => return Err(CompileError::not_constant(span)),
+    })
+}
+
+// Integers narrower than 64 bits wrap around at their own width. Unsigned integers are compared,
+// divided and shifted as such.
+fn evaluate_integer(
+    operator: &BinaryOperator,
+    left: i64,
+    right: i64,
+    ty: IntegerType,
+    span: Span,
+) -> CompileResult<Constant> {
+    use BinaryOperator::*;
+
+    let (unsigned_left, unsigned_right) = (left as u64, right as u64);
+
+    let value = match operator {
+        Plus => left.wrapping_add(right),
+        Minus => left.wrapping_sub(right),
+        Asterisk => left.wrapping_mul(right),
+        Slash | Percent if right == 0 => return Err(CompileError::division_by_zero(span)),
+        Slash if ty.signed => left.wrapping_div(right),
+        Slash => (unsigned_left / unsigned_right) as i64,
+        Percent if ty.signed => left.wrapping_rem(right),
+        Percent => (unsigned_left % unsigned_right) as i64,
+        EQ => return Ok(Constant::Boolean(left == right)),
+        NEQ => return Ok(Constant::Boolean(left != right)),
+        LT | GT | LTE | GTE => {
+            let ordering = if ty.signed {
+                left.cmp(&right)
+            } else {
+                unsigned_left.cmp(&unsigned_right)
+            };
+
+            return Ok(Constant::Boolean(match operator {
+                LT => ordering.is_lt(),
+                GT => ordering.is_gt(),
+                LTE => ordering.is_le(),
+                _ => ordering.is_ge(),
+            }));
+        }
+        BitAnd => left & right,
+        BitOr => left | right,
+        BitXor => left ^ right,
+        Shl | Shr if !(0..ty.bits as i64).contains(&right) => {
+            return Err(CompileError::shift_out_of_range(right, span))
+        }
+        Shl => left << right,
+        Shr if ty.signed => left >> right,
+        Shr => (unsigned_left >> right) as i64,
+        _ => return Err(CompileError::expected("boolean", span)),
+    };
+
+    Ok(Constant::Integer(truncate(value, ty), ty))
+}
+
+// Wraps `value` around to the width of `ty`, extending it back to 64 bits.
+fn truncate(value: i64, ty: IntegerType) -> i64 {
+    let shift = 64 - ty.bits;
+
+    if ty.signed {
+        (value << shift) >> shift
+    } else {
+        ((value as u64) << shift >> shift) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{
+        testing::{compile, compile_error, function},
+        CompileErrorKind,
+    };
+    use inkwell::context::Context;
+
+    #[test]
+    fn sized_integer_constant() {
+        let context = Context::create();
+        let module = compile(&context, "const MIN = -128i8\n\ndefine f -> i8 = MIN + 1i8");
+
+        let entry = function(&module, "f").get_first_basic_block().unwrap();
+        let returned = entry
+            .get_terminator()
+            .and_then(|terminator| terminator.get_operand(0)?.left())
+            .unwrap()
+            .into_int_value();
+        assert_eq!(returned.get_type().get_bit_width(), 8);
+        assert_eq!(returned.get_sign_extended_constant(), Some(-127));
+
+        assert!(matches!(
+            compile_error("const WIDE = 1u8 << 8u8"),
+            CompileErrorKind::ShiftOutOfRange(_)
+        ));
+    }
+}
@@ -1,10 +1,27 @@
-use super::{types::AstType, CompileError, CompileResult, Literal};
+use super::{
+    symbol_table::{FunctionEntry, GenericFunctionEntry, SymbolTable, VariableEntry},
+    types::{ArrayType, AstType, EnumType, FunctionType, IntegerType, StructType},
+    Block, CompileError, CompileResult, Identifier, IntLiteral, Literal, Parameter, Statement,
+    StringLiteral,
+};
 use crate::{
-    BinaryOperator, CodegenType, Compiler, DisplayNode, ExpressionCodegen, Span, UnaryOperator,
-    Value,
+    display, BinaryOperator, CodegenType, Compiler, CurrentFunction, DisplayNode,
+    ExpressionCodegen, Span, StatementCodegen, UnaryOperator, Value,
+};
+use inkwell::{
+    basic_block::BasicBlock,
+    module::Linkage,
+    types,
+    values::{
+        AggregateValue, BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue,
+        IntValue, PointerValue,
+    },
+    AddressSpace, FloatPredicate, IntPredicate,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
 };
-use inkwell::values::{BasicMetadataValueEnum, BasicValue};
-use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Expression {
@@ -14,12 +31,16 @@ pub enum Expression {
     Assign(AssignExpression),
     Call(CallExpression),
     Index(IndexExpression),
+    Slice(SliceExpression),
     Typeof(TypeofExpression),
     Sizeof(SizeofExpression),
     Cast(CastExpression),
     Pointer(PointerExpression),
     Dereference(DereferenceExpression),
     Ternary(TernaryExpression),
+    Match(MatchExpression),
+    Lambda(LambdaExpression),
+    New(NewExpression),
 }
 
 impl ExpressionCodegen for Expression {
@@ -33,7 +54,7 @@ impl ExpressionCodegen for Expression {
                 }
             };
         }
-        inner! { Literal Binary Unary Assign Call Index Typeof Sizeof Cast Dereference Pointer Ternary }
+        inner! { Literal Binary Unary Assign Call Index Slice Typeof Sizeof Cast Dereference Pointer Ternary Match Lambda New }
     }
 }
 
@@ -50,7 +71,7 @@ impl From<Expression> for Span {
             };
         }
 
-        inner! { Binary Unary Assign Call Index Typeof Sizeof Cast Dereference Pointer Ternary }
+        inner! { Binary Unary Assign Call Index Slice Typeof Sizeof Cast Dereference Pointer Ternary Match Lambda New }
     }
 }
 
@@ -66,7 +87,7 @@ impl DisplayNode for Expression {
             };
         }
 
-        inner! { Literal Binary Unary Assign Call Index Typeof Sizeof Cast Dereference Pointer Ternary }
+        inner! { Literal Binary Unary Assign Call Index Slice Typeof Sizeof Cast Dereference Pointer Ternary Match Lambda New }
     }
 }
 
@@ -85,15 +106,37 @@ impl ExpressionCodegen for BinaryExpression {
             Dot => self.codegen_dot(compiler),
             Plus | Minus | Asterisk | Slash | Percent => self.codegen_arithmetic(compiler),
             EQ | NEQ | LT | GT | LTE | GTE => self.codegen_comparison(compiler),
+            And | Or => self.codegen_logical(compiler),
+            BitAnd | BitOr | BitXor | Shl | Shr => self.codegen_bitwise(compiler),
         }
     }
 }
 
 impl BinaryExpression {
     fn codegen_dot<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        if let Expression::Call(call) = &*self.right {
+            return codegen_method_call(compiler, &self.left, call, self.span);
+        }
+
+        if let Expression::Literal(Literal::Int(index)) = &*self.right {
+            return self.codegen_tuple_index(compiler, index);
+        }
+
         let left = self.left.codegen(compiler)?;
-        let left_ty = match left.ty {
-            CodegenType::Struct(struct_type) => struct_type,
+        let (left, left_ty) = match left.ty {
+            CodegenType::Struct(struct_type) => (left.llvm_value, struct_type),
+            // Pointers to structs, such as `self`, are dereferenced implicitly.
+            CodegenType::Pointer(ty) => match *ty {
+                CodegenType::Struct(struct_type) => (
+                    compiler.builder.build_load(
+                        struct_type.to_llvm_struct_type(compiler.context),
+                        left.llvm_value.into_pointer_value(),
+                        "deref",
+                    ),
+                    struct_type,
+                ),
+                _ => return Err(CompileError::member_access_non_struct_type(self.span)),
+            },
             _ => return Err(CompileError::member_access_non_struct_type(self.span)),
         };
 
@@ -106,74 +149,245 @@ impl BinaryExpression {
             Some(field) => field,
             None => return Err(CompileError::field_not_found(right.identifier, right.span)),
         };
-        let field_ll_ty = field.1.to_llvm_type(compiler.context);
 
-        let ptr = unsafe {
-            compiler.builder.build_gep(
-                CodegenType::Struct(left_ty.clone()).to_llvm_type(compiler.context),
-                left.llvm_value.into_pointer_value(),
-                &[compiler.context.i64_type().const_int(field.0 as u64, false)],
-                format!("ptr.struct.{}.{}", left_ty.name, field.0).as_str(),
+        let value = compiler
+            .builder
+            .build_extract_value(
+                left.into_struct_value(),
+                field.0 as u32,
+                format!("struct.{}.{}", left_ty.name, field.0).as_str(),
             )
-        };
-
-        let load = compiler.builder.build_load(
-            field_ll_ty,
-            ptr,
-            format!("struct.{}.{}", left_ty.name, field.0).as_str(),
-        );
+            .unwrap();
 
         Ok(Value::new(
-            load,
+            value,
             left_ty.fields.get(&right.identifier).unwrap().1.clone(),
         ))
     }
 
-    fn codegen_arithmetic<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+    fn codegen_tuple_index<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        index: &IntLiteral,
+    ) -> CompileResult<Value<'a>> {
         let left = self.left.codegen(compiler)?;
-        let right = self.right.codegen(compiler)?;
-
-        let left = match left.ty {
-            CodegenType::Int => left.llvm_value.into_int_value(),
+        let elements = match left.ty {
+            CodegenType::Tuple(elements) => elements,
             _ => {
                 return Err(CompileError::expected(
-                    "int",
+                    "tuple",
                     Span::from(*self.left.clone()),
                 ))
             }
         };
 
-        let right = match right.ty {
-            CodegenType::Int => right.llvm_value.into_int_value(),
-            _ => {
-                return Err(CompileError::expected(
-                    "int",
-                    Span::from(*self.right.clone()),
-                ))
-            }
+        let ty = match elements.get(index.value as usize) {
+            Some(ty) if index.ty.is_none() => ty.clone(),
+            _ => return Err(CompileError::field_not_found(index.value, index.span)),
         };
 
+        let value = compiler
+            .builder
+            .build_extract_value(
+                left.llvm_value.into_struct_value(),
+                index.value as u32,
+                format!("tuple.{}", index.value).as_str(),
+            )
+            .unwrap();
+
+        Ok(Value::new(value, ty))
+    }
+
+    fn codegen_operands<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+    ) -> CompileResult<(Value<'a>, Value<'a>)> {
+        let left = self.left.codegen(compiler)?;
+        let right = self.right.codegen(compiler)?;
+
+        let is_number = |ty: &CodegenType| ty.is_integer() || ty.is_float();
+
+        match (&left.ty, &right.ty) {
+            (left_ty, right_ty) if is_number(left_ty) && left_ty == right_ty => Ok((left, right)),
+            (left_ty, right_ty) if is_number(left_ty) && is_number(right_ty) => Err(
+                CompileError::type_mismatch(left.ty, right.ty, Span::from(*self.right.clone())),
+            ),
+            (left_ty, _) if is_number(left_ty) => Err(CompileError::expected(
+                "int or float",
+                Span::from(*self.right.clone()),
+            )),
+            _ => Err(CompileError::expected(
+                "int or float",
+                Span::from(*self.left.clone()),
+            )),
+        }
+    }
+
+    fn codegen_arithmetic<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let (left, right) = self.codegen_operands(compiler)?;
+
         use BinaryOperator::*;
 
+        if left.ty.is_float() {
+            let ty = left.ty;
+            let left = left.llvm_value.into_float_value();
+            let right = right.llvm_value.into_float_value();
+
+            let result = match self.operator {
+                Plus => compiler.builder.build_float_add(left, right, "add"),
+                Minus => compiler.builder.build_float_sub(left, right, "sub"),
+                Asterisk => compiler.builder.build_float_mul(left, right, "mul"),
+                Slash => compiler.builder.build_float_div(left, right, "div"),
+                Percent => compiler.builder.build_float_rem(left, right, "rem"),
+                _ => unreachable!(),
+            };
+
+            return Ok(Value::new(result.into(), ty));
+        }
+
+        let ty = left.ty;
+        let signed = ty.integer_type().unwrap().signed;
+        let left = left.llvm_value.into_int_value();
+        let right = right.llvm_value.into_int_value();
+
         let result = match self.operator {
             Plus => compiler.builder.build_int_add(left, right, "add"),
             Minus => compiler.builder.build_int_sub(left, right, "sub"),
             Asterisk => compiler.builder.build_int_mul(left, right, "mul"),
-            Slash => compiler.builder.build_int_signed_div(left, right, "div"),
-            Percent => compiler.builder.build_int_signed_rem(left, right, "rem"),
+            Slash if signed => compiler.builder.build_int_signed_div(left, right, "div"),
+            Slash => compiler.builder.build_int_unsigned_div(left, right, "div"),
+            Percent if signed => compiler.builder.build_int_signed_rem(left, right, "rem"),
+            Percent => compiler.builder.build_int_unsigned_rem(left, right, "rem"),
             _ => unreachable!(),
         };
 
-        Ok(Value::new(result.into(), CodegenType::Int))
+        Ok(Value::new(result.into(), ty))
     }
 
     fn codegen_comparison<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let (left, right) = self.codegen_operands(compiler)?;
+
+        use BinaryOperator::*;
+
+        if left.ty.is_float() {
+            let left = left.llvm_value.into_float_value();
+            let right = right.llvm_value.into_float_value();
+
+            let (predicate, name) = match self.operator {
+                EQ => (FloatPredicate::OEQ, "eq"),
+                NEQ => (FloatPredicate::ONE, "ne"),
+                LT => (FloatPredicate::OLT, "lt"),
+                GT => (FloatPredicate::OGT, "gt"),
+                LTE => (FloatPredicate::OLE, "lte"),
+                GTE => (FloatPredicate::OGE, "gte"),
+                _ => unreachable!(),
+            };
+
+            let result = compiler
+                .builder
+                .build_float_compare(predicate, left, right, name);
+
+            return Ok(Value::new(result.into(), CodegenType::Boolean));
+        }
+
+        let signed = left.ty.integer_type().unwrap().signed;
+        let left = left.llvm_value.into_int_value();
+        let right = right.llvm_value.into_int_value();
+
+        let (predicate, name) = match (&self.operator, signed) {
+            (EQ, _) => (IntPredicate::EQ, "eq"),
+            (NEQ, _) => (IntPredicate::NE, "ne"),
+            (LT, true) => (IntPredicate::SLT, "lt"),
+            (GT, true) => (IntPredicate::SGT, "gt"),
+            (LTE, true) => (IntPredicate::SLE, "lte"),
+            (GTE, true) => (IntPredicate::SGE, "gte"),
+            (LT, false) => (IntPredicate::ULT, "lt"),
+            (GT, false) => (IntPredicate::UGT, "gt"),
+            (LTE, false) => (IntPredicate::ULE, "lte"),
+            (GTE, false) => (IntPredicate::UGE, "gte"),
+            _ => unreachable!(),
+        };
+
+        let result = compiler
+            .builder
+            .build_int_compare(predicate, left, right, name);
+
+        Ok(Value::new(result.into(), CodegenType::Boolean))
+    }
+
+    fn codegen_logical<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let left = self.left.codegen(compiler)?;
+        if left.ty != CodegenType::Boolean {
+            return Err(CompileError::type_mismatch(
+                CodegenType::Boolean,
+                left.ty,
+                Span::from(*self.left.clone()),
+            ));
+        }
+
+        let function = compiler.current_function.clone().unwrap().function;
+        let is_and = matches!(self.operator, BinaryOperator::And);
+        let name = if is_and { "and" } else { "or" };
+
+        let left_block = compiler.builder.get_insert_block().unwrap();
+        let right_block = compiler
+            .context
+            .append_basic_block(function, format!("{name}.rhs").as_str());
+        let merge_block = compiler
+            .context
+            .append_basic_block(function, format!("{name}.merge").as_str());
+
+        // `A && B` only evaluates `B` when `A` is true, `A || B` only when `A` is false.
+        let (then_block, else_block) = if is_and {
+            (right_block, merge_block)
+        } else {
+            (merge_block, right_block)
+        };
+        compiler.builder.build_conditional_branch(
+            left.llvm_value.into_int_value(),
+            then_block,
+            else_block,
+        );
+
+        compiler.builder.position_at_end(right_block);
+        let right = self.right.codegen(compiler)?;
+        if right.ty != CodegenType::Boolean {
+            return Err(CompileError::type_mismatch(
+                CodegenType::Boolean,
+                right.ty,
+                Span::from(*self.right.clone()),
+            ));
+        }
+        compiler.builder.build_unconditional_branch(merge_block);
+
+        let right_block = compiler.builder.get_insert_block().unwrap();
+
+        compiler.builder.position_at_end(merge_block);
+
+        let phi = compiler
+            .builder
+            .build_phi(compiler.context.bool_type(), name);
+        phi.add_incoming(&[
+            (
+                &compiler
+                    .context
+                    .bool_type()
+                    .const_int(!is_and as u64, false),
+                left_block,
+            ),
+            (&right.llvm_value, right_block),
+        ]);
+
+        Ok(Value::new(phi.as_basic_value(), CodegenType::Boolean))
+    }
+
+    fn codegen_bitwise<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
         let left = self.left.codegen(compiler)?;
         let right = self.right.codegen(compiler)?;
 
-        let left = match left.ty {
-            CodegenType::Int => left.llvm_value.into_int_value(),
-            _ => {
+        let signed = match left.ty.integer_type() {
+            Some(ty) => ty.signed,
+            None => {
                 return Err(CompileError::expected(
                     "int",
                     Span::from(*self.left.clone()),
@@ -181,45 +395,51 @@ impl BinaryExpression {
             }
         };
 
-        let right = match right.ty {
-            CodegenType::Int => right.llvm_value.into_int_value(),
-            _ => {
-                return Err(CompileError::expected(
-                    "int",
-                    Span::from(*self.right.clone()),
-                ))
-            }
-        };
+        if !right.ty.is_integer() {
+            return Err(CompileError::expected(
+                "int",
+                Span::from(*self.right.clone()),
+            ));
+        }
+
+        if left.ty != right.ty {
+            return Err(CompileError::type_mismatch(
+                left.ty,
+                right.ty,
+                Span::from(*self.right.clone()),
+            ));
+        }
+
+        let ty = left.ty;
+        let left = left.llvm_value.into_int_value();
+        let right = right.llvm_value.into_int_value();
 
         use BinaryOperator::*;
 
+        // The shift amount wraps around the bit width, as shifting by the width or more would give
+        // an undefined result.
+        let amount = || {
+            let mask = left.get_type().get_bit_width() as u64 - 1;
+            compiler.builder.build_and(
+                right,
+                left.get_type().const_int(mask, false),
+                "shift.amount",
+            )
+        };
+
+        // Shifting an unsigned integer right fills it with zeros rather than its sign bit.
         let result = match self.operator {
-            EQ => compiler
-                .builder
-                .build_int_compare(inkwell::IntPredicate::EQ, left, right, "eq"),
-            NEQ => compiler
-                .builder
-                .build_int_compare(inkwell::IntPredicate::NE, left, right, "ne"),
-            LT => compiler
-                .builder
-                .build_int_compare(inkwell::IntPredicate::SLT, left, right, "lt"),
-            GT => compiler
+            BitAnd => compiler.builder.build_and(left, right, "and"),
+            BitOr => compiler.builder.build_or(left, right, "or"),
+            BitXor => compiler.builder.build_xor(left, right, "xor"),
+            Shl => compiler.builder.build_left_shift(left, amount(), "shl"),
+            Shr => compiler
                 .builder
-                .build_int_compare(inkwell::IntPredicate::SGT, left, right, "gt"),
-            LTE => {
-                compiler
-                    .builder
-                    .build_int_compare(inkwell::IntPredicate::SLE, left, right, "lte")
-            }
-            GTE => {
-                compiler
-                    .builder
-                    .build_int_compare(inkwell::IntPredicate::SGE, left, right, "gte")
-            }
+                .build_right_shift(left, amount(), signed, "shr"),
             _ => unreachable!(),
         };
 
-        Ok(Value::new(result.into(), CodegenType::Boolean))
+        Ok(Value::new(result.into(), ty))
     }
 }
 
@@ -247,6 +467,7 @@ impl ExpressionCodegen for UnaryExpression {
         match self.operator {
             Minus => self.codegen_minus(compiler),
             Not => self.codegen_not(compiler),
+            BitNot => self.codegen_bit_not(compiler),
         }
     }
 }
@@ -256,17 +477,17 @@ impl UnaryExpression {
         let expression = self.expression.codegen(compiler)?;
 
         Ok(match expression.ty {
-            CodegenType::Int => {
+            ty if ty.is_integer() => {
                 let result = compiler
                     .builder
                     .build_int_neg(expression.llvm_value.into_int_value(), "neg");
-                Value::new(result.into(), CodegenType::Int)
+                Value::new(result.into(), ty)
             }
-            CodegenType::Float => {
+            ty if ty.is_float() => {
                 let result = compiler
                     .builder
                     .build_float_neg(expression.llvm_value.into_float_value(), "neg");
-                Value::new(result.into(), CodegenType::Float)
+                Value::new(result.into(), ty)
             }
             _ => return Err(CompileError::expected("int or float", self.span)),
         })
@@ -283,6 +504,19 @@ impl UnaryExpression {
         let result = compiler.builder.build_not(expression, "not");
         Ok(Value::new(result.into(), CodegenType::Boolean))
     }
+
+    fn codegen_bit_not<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let expression = self.expression.codegen(compiler)?;
+
+        if !expression.ty.is_integer() {
+            return Err(CompileError::expected("int", self.span));
+        }
+
+        let result = compiler
+            .builder
+            .build_not(expression.llvm_value.into_int_value(), "bit_not");
+        Ok(Value::new(result.into(), expression.ty))
+    }
 }
 
 impl DisplayNode for UnaryExpression {
@@ -315,11 +549,16 @@ impl ExpressionCodegen for AssignExpression {
                             ));
                         }
 
-                        compiler
-                            .builder
-                            .build_store(entry.pointer, value.llvm_value);
+                        store_assigned(compiler, entry.pointer, &value);
                         value
                     }
+                    None if compiler
+                        .symbol_table
+                        .get_constant(&identifier.identifier)
+                        .is_some() =>
+                    {
+                        return Err(CompileError::cannot_be_assigned(self.span))
+                    }
                     None => {
                         return Err(CompileError::identifier_not_found(
                             identifier.identifier,
@@ -329,84 +568,36 @@ impl ExpressionCodegen for AssignExpression {
                 }
             }
             Expression::Index(index) => {
-                let left = index.left.codegen(compiler)?;
-                let index = index.index.codegen(compiler)?;
-
-                match left.ty {
-                    CodegenType::Array(array) => {
-                        let index = match index.ty {
-                            CodegenType::Int => index.llvm_value.into_int_value(),
-                            _ => return Err(CompileError::expected("int", self.span)),
-                        };
-                        let element_ll_ty = array.ty.to_llvm_type(compiler.context);
-
-                        let ptr = unsafe {
-                            compiler.builder.build_gep(
-                                element_ll_ty,
-                                left.llvm_value.into_pointer_value(),
-                                &[index],
-                                "ptr_array_index",
-                            )
-                        };
-
-                        if *array.ty != value.ty {
-                            return Err(CompileError::type_mismatch(
-                                *array.ty, value.ty, self.span,
-                            ));
-                        }
+                let (ptr, ty) = index.codegen_element_pointer(compiler)?;
 
-                        compiler.builder.build_store(ptr, value.llvm_value);
-                        value
-                    }
-                    _ => {
-                        return Err(CompileError::type_that_cannot_be_indexed(
-                            (*self.expression.clone()).into(),
-                        ))
-                    }
+                if ty != value.ty {
+                    return Err(CompileError::type_mismatch(ty, value.ty, self.span));
                 }
+
+                store_assigned(compiler, ptr, &value);
+                value
             }
             Expression::Binary(BinaryExpression {
-                left,
                 operator: BinaryOperator::Dot,
-                right,
                 span,
+                ..
             }) => {
-                let left = left.codegen(compiler)?;
-                let left_ty = match left.ty {
-                    CodegenType::Struct(struct_type) => struct_type,
-                    _ => return Err(CompileError::member_access_non_struct_type(span)),
-                };
-
-                let right = match *right.clone() {
-                    Expression::Literal(Literal::Identifier(identifier)) => identifier,
-                    _ => return Err(CompileError::expected("identifier", span)),
-                };
-
-                let field = match left_ty.fields.get(&right.identifier) {
-                    Some(field) => field,
-                    None => {
-                        return Err(CompileError::field_not_found(right.identifier, right.span))
-                    }
-                };
-
-                let ptr = unsafe {
-                    compiler.builder.build_gep(
-                        CodegenType::Struct(left_ty.clone()).to_llvm_type(compiler.context),
-                        left.llvm_value.into_pointer_value(),
-                        &[compiler.context.i64_type().const_int(field.0 as u64, false)],
-                        format!("ptr.struct.{}.{}", left_ty.name, field.0).as_str(),
-                    )
+                // Fields are assigned in place, through the address of the struct they belong to.
+                let ptr = PointerExpression {
+                    expression: self.expression.clone(),
+                    span,
+                }
+                .codegen(compiler)?;
+                let field_ty = match ptr.ty {
+                    CodegenType::Pointer(ty) => *ty,
+                    _ => unreachable!(),
                 };
 
-                if field.1 != value.ty {
-                    return Err(CompileError::type_mismatch(
-                        field.1.clone(),
-                        value.ty,
-                        self.span,
-                    ));
+                if field_ty != value.ty {
+                    return Err(CompileError::type_mismatch(field_ty, value.ty, self.span));
                 }
 
-                compiler.builder.build_store(ptr, value.llvm_value);
+                store_assigned(compiler, ptr.llvm_value.into_pointer_value(), &value);
                 value
             }
             Expression::Dereference(dereference) => {
@@ -422,9 +613,10 @@ impl ExpressionCodegen for AssignExpression {
                             ));
                         }
 
-                        compiler.builder.build_store(
+                        store_assigned(
+                            compiler,
                             expression.llvm_value.into_pointer_value(),
-                            value.llvm_value,
+                            &value,
                         );
                         value
                     }
@@ -436,6 +628,23 @@ impl ExpressionCodegen for AssignExpression {
     }
 }
 
+// The place assigned to owns the value stored in it, and gives up the one it replaces.
+fn store_assigned<'a>(compiler: &Compiler<'a>, pointer: PointerValue<'a>, value: &Value<'a>) {
+    if !value.ty.holds_references() {
+        compiler.builder.build_store(pointer, value.llvm_value);
+        return;
+    }
+
+    compiler.retain_value(value);
+
+    let previous =
+        compiler
+            .builder
+            .build_load(value.ty.to_llvm_type(compiler.context), pointer, "previous");
+    compiler.builder.build_store(pointer, value.llvm_value);
+    compiler.release_value(&Value::new(previous, value.ty.clone()));
+}
+
 impl DisplayNode for AssignExpression {
     fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
         self.expression.display(f, indent)?;
@@ -453,137 +662,745 @@ pub struct CallExpression {
 
 impl ExpressionCodegen for CallExpression {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        let (function, entry) = match *self.function.clone() {
-            Expression::Literal(Literal::Identifier(identifier)) => {
-                let function = match compiler.symbol_table.get_function(&identifier.identifier) {
-                    Some(entry) => entry,
-                    None => {
-                        return Err(CompileError::function_not_found(
-                            identifier.identifier,
-                            identifier.span,
-                        ))
-                    }
-                };
-                let value = match compiler.module.get_function(&function.name) {
-                    Some(value) => value,
-                    None => {
-                        return Err(CompileError::function_not_found(
-                            identifier.identifier,
-                            identifier.span,
-                        ))
-                    }
+        if let Expression::Binary(BinaryExpression {
+            left,
+            operator: BinaryOperator::Dot,
+            right,
+            span,
+        }) = &*self.function
+        {
+            if let Expression::Literal(Literal::Identifier(_)) = &**right {
+                let call = CallExpression {
+                    function: right.clone(),
+                    arguments: self.arguments.clone(),
+                    span: self.span,
                 };
+                return codegen_method_call(compiler, left, &call, *span);
+            }
+        }
 
-                (value, function)
+        // The callee is resolved before the arguments are generated, so that an unknown function
+        // is reported before any error in its arguments.
+        let callee = match *self.function.clone() {
+            // Variables shadow functions of the same name, and are called indirectly below.
+            Expression::Literal(Literal::Identifier(identifier))
+                if compiler
+                    .symbol_table
+                    .get_variable(&identifier.identifier)
+                    .is_none() =>
+            {
+                match compiler.symbol_table.get_function(&identifier.identifier) {
+                    Some(entry) => Callee::Function(entry),
+                    None => match compiler
+                        .symbol_table
+                        .get_generic_function(&identifier.identifier)
+                    {
+                        Some(entry) => Callee::Generic(entry),
+                        // `len` is not reserved, so it is only built in if no function shadows it.
+                        None if identifier.identifier == "len" => {
+                            return codegen_len(compiler, &self.arguments, self.span)
+                        }
+                        None => {
+                            return Err(CompileError::function_not_found(
+                                identifier.identifier,
+                                identifier.span,
+                            ))
+                        }
+                    },
+                }
             }
-            _ => return Err(CompileError::call_non_function_type(self.span)),
+            function => Callee::Value(function.codegen(compiler)?),
         };
 
-        if self.arguments.len() != entry.function_type.parameters.len() {
-            return Err(CompileError::wrong_number_of_arguments(
-                entry.function_type.parameters.len(),
-                self.arguments.len(),
-                self.span,
-            ));
-        }
-
-        let mut arguments: Vec<BasicMetadataValueEnum> = Vec::new();
+        let mut values = Vec::new();
 
         for argument in self.arguments.clone() {
-            let value = argument.codegen(compiler)?;
-            arguments.push(value.llvm_value.into());
-
-            let paramter_ty = entry.function_type.parameters[arguments.len() - 1].clone();
-            if value.ty != paramter_ty {
-                return Err(CompileError::type_mismatch(
-                    paramter_ty,
-                    value.ty,
-                    argument.into(),
-                ));
-            }
+            values.push(argument.codegen(compiler)?);
         }
 
-        Ok(
-            match compiler
-                .builder
-                .build_call(function, arguments.as_slice(), "call")
-                .try_as_basic_value()
-                .left()
-            {
-                Some(value) => Value::new(value, *entry.function_type.return_type.clone()),
-                None => Value::new(
-                    compiler.context.i64_type().const_int(0, false).into(),
-                    CodegenType::Int, // Void
-                ),
-            },
+        let function = match callee {
+            Callee::Function(entry) => entry,
+            // Generic functions are instantiated for the types of the arguments.
+            Callee::Generic(entry) => {
+                let argument_types = values
+                    .iter()
+                    .map(|value| value.ty.clone())
+                    .collect::<Vec<_>>();
+                entry.instantiate(compiler, &argument_types, self.span)?
+            }
+            Callee::Value(callee) => {
+                return call_value(compiler, callee, &self.arguments, values, self.span);
+            }
+        };
+        let value = match compiler.module.get_function(&function.name) {
+            Some(value) => value,
+            None => return Err(CompileError::function_not_found(function.name, self.span)),
+        };
+
+        build_call(
+            compiler,
+            value.as_global_value().as_pointer_value(),
+            &function.function_type,
+            &self.arguments,
+            values,
+            self.span,
         )
     }
 }
 
-impl DisplayNode for CallExpression {
-    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
-        self.function.display(f, indent)?;
-        write!(f, "(")?;
-        for (i, argument) in self.arguments.iter().enumerate() {
-            if i != 0 {
-                write!(f, ", ")?;
-            }
-            argument.display(f, indent)?;
+enum Callee<'a> {
+    Function(FunctionEntry<'a>),
+    Generic(GenericFunctionEntry<'a>),
+    Value(Value<'a>),
+}
+
+// The built-in `len(value)`, the number of elements of an array or vec, or of bytes of a string.
+fn codegen_len<'a>(
+    compiler: &mut Compiler<'a>,
+    arguments: &[Expression],
+    span: Span,
+) -> CompileResult<Value<'a>> {
+    let [argument] = arguments else {
+        return Err(CompileError::wrong_number_of_arguments(
+            1,
+            arguments.len(),
+            span,
+        ));
+    };
+    let value = argument.codegen(compiler)?;
+
+    match value.ty {
+        CodegenType::Array(_) => {
+            let (_, len) = compiler.array_parts(&value);
+            Ok(Value::new(len.into(), CodegenType::Int))
         }
-        write!(f, ")")
+        CodegenType::Vec(_) => {
+            let (_, len) = compiler.vec_parts(&value);
+            Ok(Value::new(len.into(), CodegenType::Int))
+        }
+        // The length is stored right before the bytes of the string.
+        CodegenType::String => {
+            let i64_type = compiler.context.i64_type();
+            let len = unsafe {
+                compiler.builder.build_in_bounds_gep(
+                    i64_type,
+                    value.llvm_value.into_pointer_value(),
+                    &[i64_type.const_all_ones()],
+                    "ptr.str.len",
+                )
+            };
+
+            Ok(Value::new(
+                compiler.builder.build_load(i64_type, len, "str.len"),
+                CodegenType::Int,
+            ))
+        }
+        ty => Err(CompileError::type_mismatch(
+            "array, vec or str",
+            ty.to_string().as_str(),
+            argument.clone().into(),
+        )),
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct IndexExpression {
-    pub left: Box<Expression>,
-    pub index: Box<Expression>,
-    pub span: Span,
-}
+// Calls `receiver.method(arguments)` as `Struct.method(&receiver, arguments)`. Fields holding a
+// function are called with the arguments alone.
+fn codegen_method_call<'a>(
+    compiler: &mut Compiler<'a>,
+    receiver: &Expression,
+    call: &CallExpression,
+    span: Span,
+) -> CompileResult<Value<'a>> {
+    let method = match &*call.function {
+        Expression::Literal(Literal::Identifier(identifier)) => identifier,
+        _ => return Err(CompileError::expected("identifier", span)),
+    };
+
+    let pointer = PointerExpression {
+        expression: Box::new(receiver.clone()),
+        span,
+    }
+    .codegen(compiler)?;
 
-impl ExpressionCodegen for IndexExpression {
-    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        let left = self.left.codegen(compiler)?;
-        let index = self.index.codegen(compiler)?;
+    if let CodegenType::Pointer(ty) = &pointer.ty {
+        if let CodegenType::Vec(_) = **ty {
+            let vec = Value::new(
+                compiler.builder.build_load(
+                    ty.to_llvm_type(compiler.context),
+                    pointer.llvm_value.into_pointer_value(),
+                    "load.vec",
+                ),
+                *ty.clone(),
+            );
+            return codegen_vec_method(compiler, vec, method, call);
+        }
+    }
 
-        Ok(match left.ty {
-            CodegenType::Array(array) => {
-                let index = match index.ty {
-                    CodegenType::Int => index.llvm_value.into_int_value(),
-                    _ => return Err(CompileError::expected("int", self.span)),
-                };
-                let element_ll_ty = array.ty.to_llvm_type(compiler.context);
+    let (receiver, struct_type) = struct_pointer(compiler, pointer, span)?;
 
-                let ptr = unsafe {
-                    compiler.builder.build_gep(
-                        element_ll_ty,
-                        left.llvm_value.into_pointer_value(),
-                        &[index],
-                        "ptr_array_index",
-                    )
-                };
+    if let Some((index, ty @ (CodegenType::Function(_) | CodegenType::Closure(_)))) =
+        struct_type.fields.get(&method.identifier).cloned()
+    {
+        let field = compiler
+            .builder
+            .build_struct_gep(
+                struct_type.to_llvm_struct_type(compiler.context),
+                receiver,
+                index as u32,
+                format!("ptr.struct.{}.{index}", struct_type.name).as_str(),
+            )
+            .unwrap();
+        let callee = Value::new(
+            compiler
+                .builder
+                .build_load(ty.to_llvm_type(compiler.context), field, "load.field"),
+            ty,
+        );
 
-                Value::new(
-                    compiler
-                        .builder
-                        .build_load(element_ll_ty, ptr, "load_array_index"),
-                    *array.ty,
-                )
+        let mut values = Vec::new();
+        for argument in call.arguments.clone() {
+            values.push(argument.codegen(compiler)?);
+        }
+
+        return call_value(compiler, callee, &call.arguments, values, call.span);
+    }
+
+    let mut values = vec![Value::new(
+        receiver.as_basic_value_enum(),
+        CodegenType::Pointer(Box::new(CodegenType::Struct(struct_type.clone()))),
+    )];
+
+    for argument in call.arguments.clone() {
+        values.push(argument.codegen(compiler)?);
+    }
+
+    let name = format!("{}.{}", struct_type.name, method.identifier);
+    let entry = match compiler.symbol_table.get_function(&name) {
+        Some(entry) => entry,
+        None => match compiler.symbol_table.get_generic_function(&name) {
+            Some(entry) => {
+                let argument_types = values
+                    .iter()
+                    .map(|value| value.ty.clone())
+                    .collect::<Vec<_>>();
+                entry.instantiate(compiler, &argument_types, call.span)?
             }
-            _ => {
+            None => return Err(CompileError::function_not_found(name, method.span)),
+        },
+    };
+    let function = match compiler.module.get_function(&entry.name) {
+        Some(function) => function,
+        None => return Err(CompileError::function_not_found(name, method.span)),
+    };
+
+    build_call(
+        compiler,
+        function.as_global_value().as_pointer_value(),
+        &entry.function_type,
+        &call.arguments,
+        values,
+        call.span,
+    )
+}
+
+// The methods of `vec<T>` are provided by the compiler and the runtime rather than an `impl`.
+fn codegen_vec_method<'a>(
+    compiler: &mut Compiler<'a>,
+    vec: Value<'a>,
+    method: &Identifier,
+    call: &CallExpression,
+) -> CompileResult<Value<'a>> {
+    let element_type = match &vec.ty {
+        CodegenType::Vec(ty) => *ty.clone(),
+        _ => unreachable!(),
+    };
+
+    let parameters = match method.identifier.as_str() {
+        "push" => vec![element_type.clone()],
+        "pop" | "len" => Vec::new(),
+        "get" => vec![CodegenType::Int],
+        "set" => vec![CodegenType::Int, element_type.clone()],
+        _ => {
+            return Err(CompileError::function_not_found(
+                format!("{}.{}", vec.ty, method.identifier),
+                method.span,
+            ))
+        }
+    };
+
+    if call.arguments.len() != parameters.len() {
+        return Err(CompileError::wrong_number_of_arguments(
+            parameters.len(),
+            call.arguments.len(),
+            call.span,
+        ));
+    }
+
+    let mut values = Vec::new();
+    for (argument, ty) in call.arguments.iter().zip(parameters) {
+        let value = argument.codegen(compiler)?;
+        if value.ty != ty {
+            return Err(CompileError::type_mismatch(
+                ty,
+                value.ty,
+                argument.clone().into(),
+            ));
+        }
+
+        values.push(value);
+    }
+
+    let element_ll_type = element_type.to_llvm_type(compiler.context);
+    let i8_ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+
+    Ok(match method.identifier.as_str() {
+        // The runtime makes room for one more element, which is then stored in place.
+        "push" | "pop" => {
+            let function = compiler.get_or_declare_function(
+                format!("vec_{}", method.identifier).as_str(),
+                i8_ptr_type.fn_type(&[i8_ptr_type.into()], false),
+            );
+            let slot = compiler
+                .builder
+                .build_call(function, &[vec.llvm_value.into()], "vec.slot")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value();
+
+            if method.identifier == "pop" {
+                // The references held by the element are handed over to the caller.
+                let value = Value::new(
+                    compiler
+                        .builder
+                        .build_load(element_ll_type, slot, "vec.pop"),
+                    element_type,
+                );
+                compiler.register_temporary(&value);
+
+                value
+            } else {
+                compiler.retain_value(&values[0]);
+                compiler.builder.build_store(slot, values[0].llvm_value);
+
+                Value::new(
+                    compiler.context.i64_type().const_int(0, false).into(),
+                    CodegenType::Int, // Void
+                )
+            }
+        }
+        "len" => {
+            let (_, len) = compiler.vec_parts(&vec);
+            Value::new(len.into(), CodegenType::Int)
+        }
+        "get" => {
+            let (ptr, ty) = element_pointer(
+                compiler,
+                &vec,
+                values[0].llvm_value.into_int_value(),
+                call.arguments[0].clone().into(),
+            );
+
+            Value::new(
+                compiler.builder.build_load(element_ll_type, ptr, "vec.get"),
+                ty,
+            )
+        }
+        _ => {
+            let (ptr, _) = element_pointer(
+                compiler,
+                &vec,
+                values[0].llvm_value.into_int_value(),
+                call.arguments[0].clone().into(),
+            );
+            store_assigned(compiler, ptr, &values[1]);
+
+            Value::new(
+                compiler.context.i64_type().const_int(0, false).into(),
+                CodegenType::Int, // Void
+            )
+        }
+    })
+}
+
+// Applies the C default argument promotions to an argument matching the `...` of a variadic
+// function. `int` and `float` are already 64-bit, so only booleans, integers narrower than a C
+// `int` and `f32` are widened.
+fn promote_variadic_argument<'a>(
+    compiler: &Compiler<'a>,
+    value: Value<'a>,
+) -> BasicMetadataValueEnum<'a> {
+    match value.ty {
+        CodegenType::Boolean => compiler
+            .builder
+            .build_int_z_extend(
+                value.llvm_value.into_int_value(),
+                compiler.context.i32_type(),
+                "promote",
+            )
+            .into(),
+        CodegenType::F32 => compiler
+            .builder
+            .build_float_ext(
+                value.llvm_value.into_float_value(),
+                compiler.context.f64_type(),
+                "promote",
+            )
+            .into(),
+        CodegenType::Integer(ty) if ty.bits < 32 => compiler
+            .builder
+            .build_int_cast_sign_flag(
+                value.llvm_value.into_int_value(),
+                compiler.context.i32_type(),
+                ty.signed,
+                "promote",
+            )
+            .into(),
+        _ => value.llvm_value.into(),
+    }
+}
+
+// Calls the function pointer or the closure held by `callee`.
+fn call_value<'a>(
+    compiler: &mut Compiler<'a>,
+    callee: Value<'a>,
+    arguments: &[Expression],
+    mut values: Vec<Value<'a>>,
+    span: Span,
+) -> CompileResult<Value<'a>> {
+    match callee.ty {
+        CodegenType::Function(function_type) => build_call(
+            compiler,
+            callee.llvm_value.into_pointer_value(),
+            &function_type,
+            arguments,
+            values,
+            span,
+        ),
+        CodegenType::Closure(function_type) => {
+            let closure = callee.llvm_value.into_struct_value();
+            let code = compiler
+                .builder
+                .build_extract_value(closure, 0, "closure.code")
+                .unwrap()
+                .into_pointer_value();
+            let environment = compiler
+                .builder
+                .build_extract_value(closure, 1, "closure.env")
+                .unwrap();
+
+            values.insert(
+                0,
+                Value::new(
+                    environment,
+                    CodegenType::Pointer(Box::new(CodegenType::Void)),
+                ),
+            );
+
+            build_call(
+                compiler,
+                code,
+                &function_type.with_environment(),
+                arguments,
+                values,
+                span,
+            )
+        }
+        _ => Err(CompileError::call_non_function_type(span)),
+    }
+}
+
+// Type-checks the arguments against the signature of the callee, then calls it through its
+// address. Values beyond `arguments`, such as the receiver of a method, are passed first.
+fn build_call<'a>(
+    compiler: &mut Compiler<'a>,
+    function: PointerValue<'a>,
+    function_type: &FunctionType,
+    arguments: &[Expression],
+    values: Vec<Value<'a>>,
+    span: Span,
+) -> CompileResult<Value<'a>> {
+    let implicit = values.len() - arguments.len();
+
+    if values.len() < function_type.parameters.len()
+        || values.len() > function_type.parameters.len() && !function_type.variadic
+    {
+        return Err(CompileError::wrong_number_of_arguments(
+            function_type.parameters.len() - implicit,
+            arguments.len(),
+            span,
+        ));
+    }
+
+    let mut llvm_arguments: Vec<BasicMetadataValueEnum> = Vec::new();
+
+    for (i, value) in values.into_iter().enumerate() {
+        let parameter_ty = match function_type.parameters.get(i) {
+            Some(ty) => ty.clone(),
+            None => {
+                llvm_arguments.push(promote_variadic_argument(compiler, value));
+                continue;
+            }
+        };
+        if value.ty != parameter_ty {
+            return Err(CompileError::type_mismatch(
+                parameter_ty,
+                value.ty,
+                match i.checked_sub(implicit) {
+                    Some(i) => arguments[i].clone().into(),
+                    None => span,
+                },
+            ));
+        }
+
+        llvm_arguments.push(value.llvm_value.into());
+    }
+
+    Ok(
+        match compiler
+            .builder
+            .build_indirect_call(
+                function_type.to_llvm_function_type(compiler.context),
+                function,
+                llvm_arguments.as_slice(),
+                "call",
+            )
+            .try_as_basic_value()
+            .left()
+        {
+            Some(value) => {
+                // Functions returning a string give the caller a reference to it.
+                let value = Value::new(value, *function_type.return_type.clone());
+                compiler.register_temporary(&value);
+
+                value
+            }
+            None => Value::new(
+                compiler.context.i64_type().const_int(0, false).into(),
+                CodegenType::Int, // Void
+            ),
+        },
+    )
+}
+
+// Returns the address of a struct, dereferencing pointers to structs such as `self` once.
+fn codegen_struct_pointer<'a>(
+    compiler: &mut Compiler<'a>,
+    expression: &Expression,
+    span: Span,
+) -> CompileResult<(PointerValue<'a>, StructType)> {
+    let pointer = PointerExpression {
+        expression: Box::new(expression.clone()),
+        span,
+    }
+    .codegen(compiler)?;
+
+    struct_pointer(compiler, pointer, span)
+}
+
+fn struct_pointer<'a>(
+    compiler: &Compiler<'a>,
+    pointer: Value<'a>,
+    span: Span,
+) -> CompileResult<(PointerValue<'a>, StructType)> {
+    let pointer_value = pointer.llvm_value.into_pointer_value();
+
+    match pointer.ty {
+        CodegenType::Pointer(ty) => match *ty {
+            CodegenType::Struct(struct_type) => Ok((pointer_value, struct_type)),
+            CodegenType::Pointer(ty) => match *ty {
+                CodegenType::Struct(struct_type) => Ok((
+                    compiler
+                        .builder
+                        .build_load(
+                            compiler.context.i8_type().ptr_type(AddressSpace::from(0)),
+                            pointer_value,
+                            "deref",
+                        )
+                        .into_pointer_value(),
+                    struct_type,
+                )),
+                _ => Err(CompileError::member_access_non_struct_type(span)),
+            },
+            _ => Err(CompileError::member_access_non_struct_type(span)),
+        },
+        _ => unreachable!(),
+    }
+}
+
+impl DisplayNode for CallExpression {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        self.function.display(f, indent)?;
+        write!(f, "(")?;
+        for (i, argument) in self.arguments.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            argument.display(f, indent)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexExpression {
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for IndexExpression {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let (ptr, ty) = self.codegen_element_pointer(compiler)?;
+
+        Ok(Value::new(
+            compiler
+                .builder
+                .build_load(ty.to_llvm_type(compiler.context), ptr, "load_array_index"),
+            ty,
+        ))
+    }
+}
+
+impl IndexExpression {
+    // The address of the indexed element, which is loaded, assigned or taken as a pointer.
+    fn codegen_element_pointer<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+    ) -> CompileResult<(PointerValue<'a>, CodegenType)> {
+        let left = self.left.codegen(compiler)?;
+        let index = self.index.codegen(compiler)?;
+
+        if !matches!(left.ty, CodegenType::Array(_) | CodegenType::Vec(_)) {
+            return Err(CompileError::type_that_cannot_be_indexed(
+                (*self.left.clone()).into(),
+            ));
+        }
+
+        let index = match index.ty {
+            CodegenType::Int => index.llvm_value.into_int_value(),
+            _ => return Err(CompileError::expected("int", self.span)),
+        };
+
+        Ok(element_pointer(
+            compiler,
+            &left,
+            index,
+            Span::from(*self.index.clone()),
+        ))
+    }
+}
+
+// The address of the element at `index` of an array or a `vec`, which must be in bounds.
+fn element_pointer<'a>(
+    compiler: &Compiler<'a>,
+    value: &Value<'a>,
+    index: IntValue<'a>,
+    span: Span,
+) -> (PointerValue<'a>, CodegenType) {
+    let (ty, (pointer, len)) = match &value.ty {
+        CodegenType::Array(array) => (*array.ty.clone(), compiler.array_parts(value)),
+        CodegenType::Vec(ty) => (*ty.clone(), compiler.vec_parts(value)),
+        _ => unreachable!(),
+    };
+
+    compiler.build_bounds_check(index, len, span);
+
+    let ptr = unsafe {
+        compiler.builder.build_gep(
+            ty.to_llvm_type(compiler.context),
+            pointer,
+            &[index],
+            "ptr_array_index",
+        )
+    };
+
+    (ptr, ty)
+}
+
+impl DisplayNode for IndexExpression {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        self.left.display(f, indent)?;
+        write!(f, "[")?;
+        self.index.display(f, indent)?;
+        write!(f, "]")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SliceExpression {
+    pub left: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for SliceExpression {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let left = self.left.codegen(compiler)?;
+
+        let array = match left.ty {
+            CodegenType::Array(ref array) => array.clone(),
+            _ => {
                 return Err(CompileError::type_that_cannot_be_indexed(
                     (*self.left.clone()).into(),
                 ))
             }
-        })
+        };
+
+        let (pointer, len) = compiler.array_parts(&left);
+        let zero = compiler.context.i64_type().const_zero();
+
+        // The bounds default to the start and the end of the array, as in `arr[..]`.
+        let mut bound = |bound: &Option<Box<Expression>>, default| match bound {
+            Some(bound) => match bound.codegen(compiler)? {
+                Value {
+                    llvm_value,
+                    ty: CodegenType::Int,
+                } => Ok(llvm_value.into_int_value()),
+                _ => Err(CompileError::expected("int", (**bound).clone().into())),
+            },
+            None => Ok(default),
+        };
+
+        let start = bound(&self.start, zero)?;
+        let end = bound(&self.end, len)?;
+
+        compiler.build_range_check(start, end, len, self.span);
+
+        let pointer = unsafe {
+            compiler.builder.build_gep(
+                array.ty.to_llvm_type(compiler.context),
+                pointer,
+                &[start],
+                "slice.ptr",
+            )
+        };
+        let len = compiler.builder.build_int_sub(end, start, "slice.len");
+
+        Ok(compiler.build_array(
+            pointer,
+            len,
+            ArrayType {
+                len: None,
+                span: self.span,
+                ..array
+            },
+        ))
     }
 }
 
-impl DisplayNode for IndexExpression {
+impl DisplayNode for SliceExpression {
     fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
         self.left.display(f, indent)?;
         write!(f, "[")?;
-        self.index.display(f, indent)?;
+        if let Some(start) = &self.start {
+            start.display(f, indent)?;
+        }
+        write!(f, "..")?;
+        if let Some(end) = &self.end {
+            end.display(f, indent)?;
+        }
         write!(f, "]")
     }
 }
@@ -610,6 +1427,12 @@ impl ExpressionCodegen for TypeofExpression {
             Function(_) => 6,
             Void => 7,
             Pointer(_) => 8,
+            Enum(_) => 9,
+            Closure(_) => 10,
+            Integer(_) => 11,
+            F32 => 12,
+            Tuple(_) => 13,
+            Vec(_) => 14,
         };
 
         Ok(Value::new(
@@ -641,12 +1464,9 @@ impl ExpressionCodegen for SizeofExpression {
         let value = self.expression.codegen(compiler)?;
 
         let size = match value.ty {
-            CodegenType::Array(array_type) => {
-                let length = match array_type.len {
-                    Some(length) => length,
-                    _ => return Err(CompileError::unknown_size(array_type.span)),
-                };
-                let length = compiler.context.i64_type().const_int(length as u64, false);
+            // The size of the elements of the array, rather than of the slice referring to them.
+            CodegenType::Array(ref array_type) => {
+                let (_, length) = compiler.array_parts(&value);
 
                 compiler.builder.build_int_mul(
                     array_type.ty.size_of(compiler.context, self.span)?,
@@ -669,148 +1489,336 @@ impl DisplayNode for SizeofExpression {
 }
 
 #[derive(Debug, Clone)]
-pub struct CastExpression {
-    pub expression: Box<Expression>,
-    pub cast_ty: AstType,
+pub struct NewExpression {
+    pub kind: NewExpressionKind,
     pub span: Span,
 }
 
-impl ExpressionCodegen for CastExpression {
+#[derive(Debug, Clone)]
+pub enum NewExpressionKind {
+    // `new Point { x: 1, y: 2 }` copies a value to the heap and evaluates to a pointer to it.
+    Value(Box<Expression>),
+    // `new int[n]` allocates `n` zeroed elements and evaluates to a slice of them.
+    Array(AstType, Box<Expression>),
+    // `new vec<int>` creates an empty `vec`, and `new Pair<int, int>` a zeroed value.
+    Type(AstType),
+}
+
+impl ExpressionCodegen for NewExpression {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        let value = self.expression.codegen(compiler)?;
-        let ty = self.cast_ty.kind.to_codegen_type(&compiler.symbol_table)?;
+        match &self.kind {
+            NewExpressionKind::Value(expression) => {
+                let value = expression.codegen(compiler)?;
 
-        let result = match ty {
-            CodegenType::Int => match value.ty {
-                CodegenType::Float => compiler
-                    .builder
-                    .build_float_to_signed_int(
-                        value.llvm_value.into_float_value(),
-                        compiler.context.i64_type(),
-                        "cast",
-                    )
-                    .as_basic_value_enum(),
-                CodegenType::Boolean => compiler
-                    .builder
-                    .build_int_z_extend(
-                        value.llvm_value.into_int_value(),
-                        compiler.context.i64_type(),
-                        "cast",
-                    )
-                    .as_basic_value_enum(),
-                CodegenType::Pointer(_) => compiler
-                    .builder
-                    .build_ptr_to_int(
-                        value.llvm_value.into_pointer_value(),
-                        compiler.context.i64_type(),
-                        "cast",
-                    )
-                    .as_basic_value_enum(),
-                _ => return Err(CompileError::expected("float", self.span)),
-            },
-            CodegenType::Float => match value.ty {
-                CodegenType::Int => compiler
-                    .builder
-                    .build_signed_int_to_float(
-                        value.llvm_value.into_int_value(),
-                        compiler.context.f64_type(),
-                        "cast",
-                    )
-                    .as_basic_value_enum(),
-                _ => return Err(CompileError::expected("int", self.span)),
-            },
-            CodegenType::Pointer(_) => match value.ty {
-                CodegenType::Int => compiler
-                    .builder
-                    .build_int_to_ptr(
-                        value.llvm_value.into_int_value(),
-                        ty.to_llvm_type(compiler.context).into_pointer_type(),
-                        "cast",
-                    )
-                    .as_basic_value_enum(),
-                _ => return Err(CompileError::expected("int", self.span)),
-            },
-            _ => return Err(CompileError::expected("int or float", self.span)),
-        };
+                let size = value.ty.size_of(compiler.context, self.span)?;
+                let pointer = allocate(compiler, size);
+                compiler.retain_value(&value);
+                compiler.builder.build_store(pointer, value.llvm_value);
 
-        Ok(Value::new(result, ty))
+                Ok(Value::new(
+                    pointer.as_basic_value_enum(),
+                    CodegenType::Pointer(Box::new(value.ty)),
+                ))
+            }
+            NewExpressionKind::Array(ty, len) => {
+                let ty = ty.kind.to_codegen_type(&compiler.symbol_table)?;
+
+                let len = match len.codegen(compiler)? {
+                    Value {
+                        llvm_value,
+                        ty: CodegenType::Int,
+                    } => llvm_value.into_int_value(),
+                    _ => return Err(CompileError::expected("int", (**len).clone().into())),
+                };
+                compiler.build_length_check(len, self.span);
+
+                let size = compiler.builder.build_int_mul(
+                    ty.size_of(compiler.context, self.span)?,
+                    len,
+                    "new.size",
+                );
+                let pointer = allocate(compiler, size);
+
+                Ok(compiler.build_array(
+                    pointer,
+                    len,
+                    ArrayType {
+                        ty: Box::new(ty),
+                        len: None,
+                        span: self.span,
+                    },
+                ))
+            }
+            NewExpressionKind::Type(ty) => match ty.kind.to_codegen_type(&compiler.symbol_table)? {
+                CodegenType::Vec(element_type) => {
+                    let vec_new = compiler.get_or_declare_function(
+                        "vec_new",
+                        compiler
+                            .context
+                            .i8_type()
+                            .ptr_type(AddressSpace::from(0))
+                            .fn_type(&[compiler.context.i64_type().into()], false),
+                    );
+                    let vec = compiler
+                        .builder
+                        .build_call(
+                            vec_new,
+                            &[element_type.size_of(compiler.context, self.span)?.into()],
+                            "vec",
+                        )
+                        .try_as_basic_value()
+                        .left()
+                        .unwrap();
+
+                    Ok(Value::new(vec, CodegenType::Vec(element_type)))
+                }
+                ty => {
+                    let pointer = allocate(compiler, ty.size_of(compiler.context, self.span)?);
+
+                    Ok(Value::new(
+                        pointer.as_basic_value_enum(),
+                        CodegenType::Pointer(Box::new(ty)),
+                    ))
+                }
+            },
+        }
     }
 }
 
-impl DisplayNode for CastExpression {
+// Allocates zeroed memory through the runtime, which aborts rather than returning null.
+fn allocate<'a>(compiler: &Compiler<'a>, size: IntValue<'a>) -> PointerValue<'a> {
+    let allocate = compiler.get_or_declare_function(
+        "allocate",
+        compiler
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::from(0))
+            .fn_type(&[compiler.context.i64_type().into()], false),
+    );
+
+    compiler
+        .builder
+        .build_call(allocate, &[size.into()], "new")
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_pointer_value()
+}
+
+impl DisplayNode for NewExpression {
     fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
-        self.expression.display(f, indent)?;
-        write!(f, " as {}", self.cast_ty.kind)
+        write!(f, "new ")?;
+        match &self.kind {
+            NewExpressionKind::Value(expression) => expression.display(f, indent),
+            NewExpressionKind::Array(ty, len) => {
+                write!(f, "{}[", ty.kind)?;
+                len.display(f, indent)?;
+                write!(f, "]")
+            }
+            NewExpressionKind::Type(ty) => write!(f, "{}", ty.kind),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct PointerExpression {
+pub struct CastExpression {
     pub expression: Box<Expression>,
+    pub cast_ty: AstType,
     pub span: Span,
 }
 
-impl ExpressionCodegen for PointerExpression {
+impl ExpressionCodegen for CastExpression {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        match *self.expression.clone() {
-            Expression::Literal(Literal::Identifier(identifier)) => {
-                let value = match compiler.symbol_table.get_variable(&identifier.identifier) {
-                    Some(entry) => entry,
-                    None => {
-                        return Err(CompileError::identifier_not_found(
-                            identifier.identifier,
-                            identifier.span,
-                        ))
-                    }
-                };
+        let value = self.expression.codegen(compiler)?;
+        let ty = self.cast_ty.kind.to_codegen_type(&compiler.symbol_table)?;
 
-                Ok(Value::new(
-                    value.pointer.as_basic_value_enum(),
-                    CodegenType::Pointer(Box::new(value.ty.clone())),
-                ))
-            }
-            Expression::Index(index) => {
-                let value = index.left.codegen(compiler)?;
-                let index = index.index.codegen(compiler)?;
+        let result = match ty.integer_type() {
+            // Integers are truncated, or extended according to the signedness of the source.
+            Some(target) => {
+                let int_type = ty.to_llvm_type(compiler.context).into_int_type();
 
                 match value.ty {
-                    CodegenType::Array(array) => {
-                        let index = match index.ty {
-                            CodegenType::Int => index.llvm_value.into_int_value(),
-                            _ => return Err(CompileError::expected("int", self.span)),
-                        };
-                        let element_ll_ty = array.ty.to_llvm_type(compiler.context);
-
-                        let ptr = unsafe {
-                            compiler.builder.build_gep(
-                                element_ll_ty,
-                                value.llvm_value.into_pointer_value(),
-                                &[index],
-                                "ptr_array_index",
-                            )
-                        };
-
-                        Ok(Value::new(
-                            ptr.as_basic_value_enum(),
-                            CodegenType::Pointer(array.ty),
-                        ))
-                    }
-                    _ => Err(CompileError::type_that_cannot_be_indexed(
-                        (*self.expression.clone()).into(),
-                    )),
+                    ref source if source.is_integer() => compiler
+                        .builder
+                        .build_int_cast_sign_flag(
+                            value.llvm_value.into_int_value(),
+                            int_type,
+                            source.integer_type().unwrap().signed,
+                            "cast",
+                        )
+                        .as_basic_value_enum(),
+                    ref source if source.is_float() && target.signed => compiler
+                        .builder
+                        .build_float_to_signed_int(
+                            value.llvm_value.into_float_value(),
+                            int_type,
+                            "cast",
+                        )
+                        .as_basic_value_enum(),
+                    ref source if source.is_float() => compiler
+                        .builder
+                        .build_float_to_unsigned_int(
+                            value.llvm_value.into_float_value(),
+                            int_type,
+                            "cast",
+                        )
+                        .as_basic_value_enum(),
+                    CodegenType::Boolean => compiler
+                        .builder
+                        .build_int_z_extend(value.llvm_value.into_int_value(), int_type, "cast")
+                        .as_basic_value_enum(),
+                    CodegenType::Pointer(_) => compiler
+                        .builder
+                        .build_ptr_to_int(value.llvm_value.into_pointer_value(), int_type, "cast")
+                        .as_basic_value_enum(),
+                    _ => return Err(CompileError::expected("float", self.span)),
+                }
+            }
+            None => self.codegen_non_integer(compiler, value, &ty)?,
+        };
+
+        Ok(Value::new(result, ty))
+    }
+}
+
+impl CastExpression {
+    fn codegen_non_integer<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        value: Value<'a>,
+        ty: &CodegenType,
+    ) -> CompileResult<BasicValueEnum<'a>> {
+        Ok(match ty {
+            ty if ty.is_float() => {
+                let float_type = ty.to_llvm_type(compiler.context).into_float_type();
+
+                match value.ty.integer_type() {
+                    Some(source) if source.signed => compiler
+                        .builder
+                        .build_signed_int_to_float(
+                            value.llvm_value.into_int_value(),
+                            float_type,
+                            "cast",
+                        )
+                        .as_basic_value_enum(),
+                    Some(_) => compiler
+                        .builder
+                        .build_unsigned_int_to_float(
+                            value.llvm_value.into_int_value(),
+                            float_type,
+                            "cast",
+                        )
+                        .as_basic_value_enum(),
+                    // `f32` and `float` are converted into each other by rounding or extending.
+                    None if value.ty.is_float() => compiler
+                        .builder
+                        .build_float_cast(value.llvm_value.into_float_value(), float_type, "cast")
+                        .as_basic_value_enum(),
+                    None => return Err(CompileError::expected("int", self.span)),
                 }
             }
+            CodegenType::Pointer(pointee) => match value.ty {
+                ref source if source.is_integer() => compiler
+                    .builder
+                    .build_int_to_ptr(
+                        value.llvm_value.into_int_value(),
+                        ty.to_llvm_type(compiler.context).into_pointer_type(),
+                        "cast",
+                    )
+                    .as_basic_value_enum(),
+                // The raw pointer to the elements of an array, as expected by C functions.
+                CodegenType::Array(ref array) if array.ty == *pointee => {
+                    compiler.array_parts(&value).0.as_basic_value_enum()
+                }
+                // A string already points to its null-terminated bytes, which stay valid for as
+                // long as the string is referred to.
+                CodegenType::String if is_byte_type(pointee) => value.llvm_value,
+                _ => return Err(CompileError::expected("int", self.span)),
+            },
+            // The bytes of a C string are copied into a new string, owned by the runtime.
+            CodegenType::String => match value.ty {
+                CodegenType::Pointer(ref pointee) if is_byte_type(pointee) => {
+                    let i8_ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+                    let str_from_cstr = compiler.get_or_declare_function(
+                        "str_from_cstr",
+                        i8_ptr_type.fn_type(&[i8_ptr_type.into()], false),
+                    );
+                    let string = compiler
+                        .builder
+                        .build_call(str_from_cstr, &[value.llvm_value.into()], "str")
+                        .try_as_basic_value()
+                        .left()
+                        .unwrap();
+                    compiler.register_temporary(&Value::new(string, CodegenType::String));
+
+                    string
+                }
+                _ => return Err(CompileError::expected("i8* or u8*", self.span)),
+            },
+            _ => return Err(CompileError::expected("int or float", self.span)),
+        })
+    }
+}
+
+fn is_byte_type(ty: &CodegenType) -> bool {
+    matches!(ty, CodegenType::Integer(IntegerType { bits: 8, .. }))
+}
+
+impl DisplayNode for CastExpression {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        self.expression.display(f, indent)?;
+        write!(f, " as {}", self.cast_ty.kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PointerExpression {
+    pub expression: Box<Expression>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for PointerExpression {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        match *self.expression.clone() {
+            Expression::Literal(Literal::Identifier(identifier)) => {
+                let value = match compiler.symbol_table.get_variable(&identifier.identifier) {
+                    Some(entry) => entry,
+                    // `&function` is the same function pointer as `function`.
+                    None if compiler
+                        .symbol_table
+                        .get_function(&identifier.identifier)
+                        .is_some() =>
+                    {
+                        return identifier.codegen(compiler)
+                    }
+                    None => {
+                        return Err(CompileError::identifier_not_found(
+                            identifier.identifier,
+                            identifier.span,
+                        ))
+                    }
+                };
+
+                Ok(Value::new(
+                    value.pointer.as_basic_value_enum(),
+                    CodegenType::Pointer(Box::new(value.ty.clone())),
+                ))
+            }
+            Expression::Index(index) => {
+                let (ptr, ty) = index.codegen_element_pointer(compiler)?;
+
+                Ok(Value::new(
+                    ptr.as_basic_value_enum(),
+                    CodegenType::Pointer(Box::new(ty)),
+                ))
+            }
             Expression::Binary(BinaryExpression {
                 left,
                 operator: BinaryOperator::Dot,
                 right,
                 span,
             }) => {
-                let left = left.codegen(compiler)?;
-                let left_ty = match left.ty {
-                    CodegenType::Struct(struct_type) => struct_type,
-                    _ => return Err(CompileError::member_access_non_struct_type(span)),
-                };
+                let (left, left_ty) = codegen_struct_pointer(compiler, &left, span)?;
 
                 let right = match *right.clone() {
                     Expression::Literal(Literal::Identifier(identifier)) => identifier,
@@ -824,14 +1832,15 @@ impl ExpressionCodegen for PointerExpression {
                     }
                 };
 
-                let ptr = unsafe {
-                    compiler.builder.build_gep(
-                        CodegenType::Struct(left_ty.clone()).to_llvm_type(compiler.context),
-                        left.llvm_value.into_pointer_value(),
-                        &[compiler.context.i64_type().const_int(field.0 as u64, false)],
+                let ptr = compiler
+                    .builder
+                    .build_struct_gep(
+                        left_ty.to_llvm_struct_type(compiler.context),
+                        left,
+                        field.0 as u32,
                         format!("ptr.struct.{}.{}", left_ty.name, field.0).as_str(),
                     )
-                };
+                    .unwrap();
 
                 Ok(Value::new(
                     ptr.as_basic_value_enum(),
@@ -841,11 +1850,13 @@ impl ExpressionCodegen for PointerExpression {
             expression => {
                 let value = expression.codegen(compiler)?;
 
+                let ptr = compiler
+                    .builder
+                    .build_alloca(value.ty.to_llvm_type(compiler.context), "ptr");
+                compiler.builder.build_store(ptr, value.llvm_value);
+
                 Ok(Value::new(
-                    compiler
-                        .builder
-                        .build_alloca(value.ty.to_llvm_type(compiler.context), "ptr")
-                        .as_basic_value_enum(),
+                    ptr.as_basic_value_enum(),
                     CodegenType::Pointer(Box::new(value.ty)),
                 ))
             }
@@ -973,3 +1984,1192 @@ impl DisplayNode for TernaryExpression {
         self.alternative.display(f, indent)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct MatchExpression {
+    pub expression: Box<Expression>,
+    pub arms: Vec<MatchArm>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard(Span),
+    Variant(VariantPattern),
+    Literal(Literal),
+    Range(RangePattern),
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantPattern {
+    pub name: Option<Identifier>,
+    pub variant: Identifier,
+    pub bindings: Vec<Identifier>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct RangePattern {
+    pub start: IntLiteral,
+    pub end: IntLiteral,
+    pub inclusive: bool,
+    pub span: Span,
+}
+
+impl Pattern {
+    fn kind(&self) -> &'static str {
+        match self {
+            Pattern::Wildcard(_) => "_",
+            Pattern::Variant(_) => "enum",
+            Pattern::Literal(Literal::Boolean(_)) => "boolean",
+            Pattern::Literal(Literal::String(_)) => "string",
+            Pattern::Literal(_) | Pattern::Range(_) => "int",
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span) => *span,
+            Pattern::Variant(pattern) => pattern.span,
+            Pattern::Literal(literal) => literal.clone().into(),
+            Pattern::Range(pattern) => pattern.span,
+        }
+    }
+}
+
+type Bindings<'a> = Vec<(Identifier, CodegenType, PointerValue<'a>)>;
+
+impl ExpressionCodegen for MatchExpression {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        match self.codegen_match(compiler, true)? {
+            Some(value) => Ok(value),
+            None => Err(CompileError::expected("expression", self.span)),
+        }
+    }
+}
+
+impl MatchExpression {
+    // When a value is needed, every arm that does not jump away must end with an expression.
+    pub fn codegen_match<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        needs_value: bool,
+    ) -> CompileResult<Option<Value<'a>>> {
+        let scrutinee = self.expression.codegen(compiler)?;
+
+        let function = compiler.current_function.clone().unwrap().function;
+
+        let arm_blocks = self
+            .arms
+            .iter()
+            .map(|_| compiler.context.append_basic_block(function, "match.arm"))
+            .collect::<Vec<_>>();
+        let merge_block = compiler.context.append_basic_block(function, "match.end");
+
+        let bindings = match scrutinee.ty.clone() {
+            CodegenType::Enum(enum_type) => {
+                self.codegen_enum_dispatch(compiler, scrutinee, enum_type, &arm_blocks)?
+            }
+            ref ty if ty.is_integer() || *ty == CodegenType::Boolean => {
+                self.codegen_switch_dispatch(compiler, scrutinee, &arm_blocks)?
+            }
+            CodegenType::String => {
+                self.codegen_string_dispatch(compiler, scrutinee, &arm_blocks)?
+            }
+            _ => {
+                return Err(CompileError::expected(
+                    "enum, int, boolean or str",
+                    (*self.expression).clone().into(),
+                ))
+            }
+        };
+
+        let mut incoming = Vec::new();
+        let mut falls_through = false;
+
+        for ((arm, block), bindings) in self.arms.iter().zip(arm_blocks).zip(bindings) {
+            compiler.builder.position_at_end(block);
+
+            let original_symbol_table = compiler.symbol_table.clone();
+            compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+
+            let value = arm.codegen_body(compiler, bindings, needs_value)?;
+
+            compiler.symbol_table = original_symbol_table;
+
+            let end_block = compiler.builder.get_insert_block().unwrap();
+            if end_block.get_terminator().is_none() {
+                compiler.builder.build_unconditional_branch(merge_block);
+                falls_through = true;
+
+                if let Some(value) = value {
+                    incoming.push((value, end_block, arm.span));
+                }
+            }
+        }
+
+        compiler.builder.position_at_end(merge_block);
+
+        if !falls_through {
+            compiler.builder.build_unreachable();
+        }
+
+        let ty = match incoming.first() {
+            Some((value, _, _)) => value.ty.clone(),
+            None => return Ok(None),
+        };
+
+        for (value, _, span) in incoming.iter() {
+            if value.ty != ty {
+                return Err(CompileError::type_mismatch(ty, value.ty.clone(), *span));
+            }
+        }
+
+        let phi = compiler
+            .builder
+            .build_phi(ty.to_llvm_type(compiler.context), "match.value");
+        for (value, block, _) in incoming.iter() {
+            phi.add_incoming(&[(&value.llvm_value, *block)]);
+        }
+
+        Ok(Some(Value::new(phi.as_basic_value(), ty)))
+    }
+
+    fn codegen_enum_dispatch<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        scrutinee: Value<'a>,
+        enum_type: EnumType,
+        arm_blocks: &[BasicBlock<'a>],
+    ) -> CompileResult<Vec<Bindings<'a>>> {
+        let enum_llvm_type = enum_type.to_llvm_struct_type(compiler.context);
+
+        let alloca = compiler
+            .builder
+            .build_alloca(enum_llvm_type, "match.scrutinee");
+        compiler.builder.build_store(alloca, scrutinee.llvm_value);
+
+        let tag_ptr = compiler
+            .builder
+            .build_struct_gep(enum_llvm_type, alloca, 0, "match.tag.ptr")
+            .unwrap();
+        let tag = compiler
+            .builder
+            .build_load(compiler.context.i32_type(), tag_ptr, "match.tag")
+            .into_int_value();
+        let payload_ptr = compiler
+            .builder
+            .build_struct_gep(enum_llvm_type, alloca, 1, "match.payload")
+            .unwrap();
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        let mut bindings = Vec::new();
+
+        for (arm, block) in self.arms.iter().zip(arm_blocks) {
+            if default.is_some() {
+                return Err(CompileError::unreachable_pattern(arm.span));
+            }
+
+            let pattern = match &arm.pattern {
+                Pattern::Wildcard(span) => {
+                    if cases.len() == enum_type.variants.len() {
+                        return Err(CompileError::unreachable_pattern(*span));
+                    }
+                    default = Some(*block);
+                    bindings.push(Vec::new());
+                    continue;
+                }
+                Pattern::Variant(pattern) => pattern,
+                pattern => {
+                    return Err(CompileError::type_mismatch(
+                        scrutinee.ty.to_string(),
+                        pattern.kind().to_string(),
+                        pattern.span(),
+                    ))
+                }
+            };
+
+            if let Some(name) = &pattern.name {
+                if name.identifier != enum_type.name {
+                    return Err(CompileError::type_mismatch(
+                        &enum_type.name,
+                        &name.identifier,
+                        name.span,
+                    ));
+                }
+            }
+
+            let (index, _, fields) = match enum_type.variants.get_full(&pattern.variant.identifier)
+            {
+                Some(variant) => variant,
+                None => {
+                    return Err(CompileError::variant_not_found(
+                        pattern.variant.identifier.clone(),
+                        pattern.variant.span,
+                    ))
+                }
+            };
+
+            if cases.iter().any(|(case, _)| *case == index) {
+                return Err(CompileError::unreachable_pattern(pattern.span));
+            }
+
+            if pattern.bindings.len() != fields.len() {
+                return Err(CompileError::wrong_number_of_fields(
+                    fields.len(),
+                    pattern.bindings.len(),
+                    pattern.span,
+                ));
+            }
+
+            let variant_llvm_type =
+                enum_type.variant_llvm_struct_type(compiler.context, &pattern.variant.identifier);
+
+            let mut arm_bindings = Vec::new();
+
+            for (i, (binding, ty)) in pattern.bindings.iter().zip(fields.values()).enumerate() {
+                if binding.identifier == "_" {
+                    continue;
+                }
+
+                let pointer = compiler
+                    .builder
+                    .build_struct_gep(
+                        variant_llvm_type,
+                        payload_ptr,
+                        i as u32,
+                        format!(
+                            "match.{}.{}",
+                            pattern.variant.identifier, binding.identifier
+                        )
+                        .as_str(),
+                    )
+                    .unwrap();
+                arm_bindings.push((binding.clone(), ty.clone(), pointer));
+            }
+
+            cases.push((index, *block));
+            bindings.push(arm_bindings);
+        }
+
+        let missing = enum_type
+            .variants
+            .keys()
+            .enumerate()
+            .filter(|(index, _)| !cases.iter().any(|(case, _)| case == index))
+            .map(|(_, name)| format!("`{name}`"))
+            .collect::<Vec<_>>();
+
+        let else_block = match default {
+            Some(block) => block,
+            None if missing.is_empty() => unreachable_block(compiler),
+            None => {
+                return Err(CompileError::non_exhaustive_patterns(
+                    missing.join(", "),
+                    self.span,
+                ))
+            }
+        };
+
+        let cases = cases
+            .into_iter()
+            .map(|(index, block)| {
+                (
+                    compiler.context.i32_type().const_int(index as u64, false),
+                    block,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        compiler.builder.build_switch(tag, else_block, &cases);
+
+        Ok(bindings)
+    }
+
+    fn codegen_switch_dispatch<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        scrutinee: Value<'a>,
+        arm_blocks: &[BasicBlock<'a>],
+    ) -> CompileResult<Vec<Bindings<'a>>> {
+        let mut cases: Vec<(i64, BasicBlock)> = Vec::new();
+        let mut ranges: Vec<(i64, i64, BasicBlock)> = Vec::new();
+        let mut default = None;
+
+        let is_covered = |cases: &[(i64, BasicBlock)], ranges: &[(i64, i64, BasicBlock)], value| {
+            cases.iter().any(|(case, _)| *case == value)
+                || ranges
+                    .iter()
+                    .any(|(start, end, _)| (*start..=*end).contains(&value))
+        };
+
+        for (arm, block) in self.arms.iter().zip(arm_blocks) {
+            if default.is_some() {
+                return Err(CompileError::unreachable_pattern(arm.span));
+            }
+
+            let value = match (&arm.pattern, &scrutinee.ty) {
+                (Pattern::Wildcard(_), _) => {
+                    default = Some(*block);
+                    continue;
+                }
+                (Pattern::Literal(Literal::Int(int)), ty) if int.ty() == *ty => int.value,
+                (Pattern::Literal(Literal::Boolean(boolean)), CodegenType::Boolean) => {
+                    boolean.value as i64
+                }
+                (Pattern::Range(range), ty) if range.start.ty() == *ty && range.end.ty() == *ty => {
+                    let end = if range.inclusive {
+                        Some(range.end.value)
+                    } else {
+                        range.end.value.checked_sub(1)
+                    };
+
+                    match end {
+                        Some(end) if range.start.value <= end => {
+                            ranges.push((range.start.value, end, *block));
+                            continue;
+                        }
+                        _ => return Err(CompileError::unreachable_pattern(range.span)),
+                    }
+                }
+                (pattern, ty) => {
+                    return Err(CompileError::type_mismatch(
+                        ty.to_string(),
+                        pattern.kind().to_string(),
+                        pattern.span(),
+                    ))
+                }
+            };
+
+            // Literals are dispatched by the switch before any range is tested, which only
+            // preserves the order of the arms if no earlier range covers them.
+            if is_covered(&cases, &ranges, value) {
+                return Err(CompileError::unreachable_pattern(arm.pattern.span()));
+            }
+
+            cases.push((value, *block));
+        }
+
+        let mut else_block = match default {
+            Some(block) => block,
+            None => {
+                let missing = match scrutinee.ty {
+                    CodegenType::Boolean => [false, true]
+                        .into_iter()
+                        .filter(|value| !is_covered(&cases, &ranges, *value as i64))
+                        .map(|value| format!("`{value}`"))
+                        .collect::<Vec<_>>(),
+                    _ => vec!["`_`".to_string()],
+                };
+
+                if !missing.is_empty() {
+                    return Err(CompileError::non_exhaustive_patterns(
+                        missing.join(", "),
+                        self.span,
+                    ));
+                }
+
+                unreachable_block(compiler)
+            }
+        };
+
+        let signed = scrutinee.ty.integer_type().is_some_and(|ty| ty.signed);
+        let value = scrutinee.llvm_value.into_int_value();
+        let int_type = value.get_type();
+
+        let function = compiler.current_function.clone().unwrap().function;
+        let dispatch_block = compiler.builder.get_insert_block().unwrap();
+
+        for (start, end, block) in ranges.into_iter().rev() {
+            let test_block = compiler.context.append_basic_block(function, "match.range");
+            compiler.builder.position_at_end(test_block);
+
+            let lower = compiler.builder.build_int_compare(
+                if signed {
+                    IntPredicate::SGE
+                } else {
+                    IntPredicate::UGE
+                },
+                value,
+                int_type.const_int(start as u64, true),
+                "match.range.start",
+            );
+            let upper = compiler.builder.build_int_compare(
+                if signed {
+                    IntPredicate::SLE
+                } else {
+                    IntPredicate::ULE
+                },
+                value,
+                int_type.const_int(end as u64, true),
+                "match.range.end",
+            );
+            let in_range = compiler.builder.build_and(lower, upper, "match.range");
+            compiler
+                .builder
+                .build_conditional_branch(in_range, block, else_block);
+
+            else_block = test_block;
+        }
+
+        compiler.builder.position_at_end(dispatch_block);
+
+        let cases = cases
+            .into_iter()
+            .map(|(value, block)| (int_type.const_int(value as u64, true), block))
+            .collect::<Vec<_>>();
+        compiler.builder.build_switch(value, else_block, &cases);
+
+        Ok(vec![Vec::new(); self.arms.len()])
+    }
+
+    fn codegen_string_dispatch<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        scrutinee: Value<'a>,
+        arm_blocks: &[BasicBlock<'a>],
+    ) -> CompileResult<Vec<Bindings<'a>>> {
+        let mut cases: Vec<(StringLiteral, BasicBlock)> = Vec::new();
+        let mut default = None;
+
+        for (arm, block) in self.arms.iter().zip(arm_blocks) {
+            if default.is_some() {
+                return Err(CompileError::unreachable_pattern(arm.span));
+            }
+
+            match &arm.pattern {
+                Pattern::Wildcard(_) => default = Some(*block),
+                Pattern::Literal(Literal::String(string)) => {
+                    if cases.iter().any(|(case, _)| case.value == string.value) {
+                        return Err(CompileError::unreachable_pattern(string.span));
+                    }
+
+                    cases.push((string.clone(), *block));
+                }
+                pattern => {
+                    return Err(CompileError::type_mismatch(
+                        CodegenType::String.to_string(),
+                        pattern.kind().to_string(),
+                        pattern.span(),
+                    ))
+                }
+            }
+        }
+
+        let default = match default {
+            Some(block) => block,
+            None => return Err(CompileError::non_exhaustive_patterns("`_`", self.span)),
+        };
+
+        let i8_ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+        let strcmp = compiler.get_or_declare_function(
+            "strcmp",
+            compiler
+                .context
+                .i32_type()
+                .fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false),
+        );
+
+        let function = compiler.current_function.clone().unwrap().function;
+
+        for (i, (string, block)) in cases.iter().enumerate() {
+            let literal = string.codegen(compiler)?;
+            let ordering = compiler
+                .builder
+                .build_call(
+                    strcmp,
+                    &[scrutinee.llvm_value.into(), literal.llvm_value.into()],
+                    "match.strcmp",
+                )
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            let equal = compiler.builder.build_int_compare(
+                IntPredicate::EQ,
+                ordering,
+                compiler.context.i32_type().const_zero(),
+                "match.str",
+            );
+
+            if i == cases.len() - 1 {
+                compiler
+                    .builder
+                    .build_conditional_branch(equal, *block, default);
+            } else {
+                let next_block = compiler.context.append_basic_block(function, "match.str");
+                compiler
+                    .builder
+                    .build_conditional_branch(equal, *block, next_block);
+                compiler.builder.position_at_end(next_block);
+            }
+        }
+
+        if cases.is_empty() {
+            compiler.builder.build_unconditional_branch(default);
+        }
+
+        Ok(vec![Vec::new(); self.arms.len()])
+    }
+}
+
+fn unreachable_block<'a>(compiler: &Compiler<'a>) -> BasicBlock<'a> {
+    let current_block = compiler.builder.get_insert_block().unwrap();
+
+    let block = compiler
+        .context
+        .insert_basic_block_after(current_block, "match.unreachable");
+    compiler.builder.position_at_end(block);
+    compiler.builder.build_unreachable();
+    compiler.builder.position_at_end(current_block);
+
+    block
+}
+
+impl MatchArm {
+    fn codegen_body<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        bindings: Bindings<'a>,
+        needs_value: bool,
+    ) -> CompileResult<Option<Value<'a>>> {
+        let (statements, tail) = match self.body.statements.split_last() {
+            Some((Statement::Expression(expression), statements)) if needs_value => {
+                (statements, Some(expression))
+            }
+            _ => (self.body.statements.as_slice(), None),
+        };
+
+        compiler.enter_scope();
+
+        // Bindings are variables of their own, which can be assigned without affecting the
+        // scrutinee.
+        for (name, ty, pointer) in bindings {
+            let value = compiler.builder.build_load(
+                ty.to_llvm_type(compiler.context),
+                pointer,
+                format!("load.{}", name.identifier).as_str(),
+            );
+            compiler.own_value(&Value::new(value, ty.clone()), pointer);
+
+            compiler
+                .symbol_table
+                .insert_variable(name.identifier, ty, pointer, name.span)?;
+        }
+
+        for statement in statements {
+            statement.codegen(compiler)?;
+        }
+
+        let terminated = compiler
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_terminator()
+            .is_some();
+
+        let value = match tail {
+            Some(expression) => {
+                // The value may be owned by a variable of the arm, which is released below, so
+                // the match keeps a reference of its own.
+                let value = expression.codegen(compiler)?;
+                compiler.retain_value(&value);
+                compiler.register_temporary(&value);
+
+                Some(value)
+            }
+            None if needs_value && !terminated => {
+                return Err(CompileError::expected("expression", self.body.span))
+            }
+            None => None,
+        };
+
+        compiler.exit_scope();
+
+        Ok(value)
+    }
+}
+
+impl DisplayNode for MatchExpression {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(f, "match ")?;
+        self.expression.display(f, indent)?;
+        for arm in self.arms.iter() {
+            writeln!(f)?;
+            display::indent(f, indent + 1)?;
+            arm.pattern.display(f, indent + 1)?;
+            write!(f, " =>")?;
+            arm.body.display(f, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl DisplayNode for Pattern {
+    fn display(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Literal(literal) => literal.display(f, 0),
+            Pattern::Range(pattern) => write!(
+                f,
+                "{}..{}{}",
+                pattern.start.value,
+                if pattern.inclusive { "=" } else { "" },
+                pattern.end.value
+            ),
+            Pattern::Variant(pattern) => {
+                if let Some(name) = &pattern.name {
+                    write!(f, "{}::", name.identifier)?;
+                }
+                write!(f, "{}", pattern.variant.identifier)?;
+                if !pattern.bindings.is_empty() {
+                    let bindings = pattern
+                        .bindings
+                        .iter()
+                        .map(|binding| binding.identifier.clone())
+                        .collect::<Vec<_>>();
+                    write!(f, "({})", bindings.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LambdaExpression {
+    pub parameters: Vec<Parameter>,
+    pub return_type: AstType,
+    pub body: Block,
+    // Every name the body refers to, including those of nested lambdas.
+    pub references: BTreeSet<String>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for LambdaExpression {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let mut parameters = Vec::new();
+        for parameter in self.parameters.iter() {
+            parameters.push(parameter.ty.kind.to_codegen_type(&compiler.symbol_table)?);
+        }
+        let return_type = self
+            .return_type
+            .kind
+            .to_codegen_type(&compiler.symbol_table)?;
+        let function_type = FunctionType {
+            name: String::new(),
+            parameters,
+            variadic: false,
+            return_type: Box::new(return_type),
+            span: self.span,
+        };
+
+        // The environment holds a copy of the local variables the body refers to, taken when the
+        // closure is created. It is allocated on the heap, so that the closure can outlive its
+        // scope. Closures are copied freely, so the environment is reference counted like a
+        // string, and releases the values it holds once it is freed.
+        let captures = compiler
+            .symbol_table
+            .locals()
+            .into_iter()
+            .filter(|(name, _)| {
+                self.references.contains(name)
+                    && self
+                        .parameters
+                        .iter()
+                        .all(|parameter| &parameter.name.identifier != name)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let environment_type = compiler.context.struct_type(
+            &captures
+                .values()
+                .map(|entry| entry.ty.to_llvm_type(compiler.context))
+                .collect::<Vec<_>>(),
+            false,
+        );
+        let i8_ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+
+        let environment = if captures.is_empty() {
+            i8_ptr_type.const_null()
+        } else {
+            let drop = if captures.values().any(|entry| entry.ty.holds_references()) {
+                let drop = compiler.detached(|compiler| {
+                    Self::codegen_drop(compiler, &captures, environment_type)
+                })?;
+                drop.as_global_value().as_pointer_value()
+            } else {
+                i8_ptr_type.const_null()
+            };
+
+            let closure_new = compiler.get_or_declare_function(
+                "closure_new",
+                i8_ptr_type.fn_type(
+                    &[compiler.context.i64_type().into(), i8_ptr_type.into()],
+                    false,
+                ),
+            );
+            let environment = compiler
+                .builder
+                .build_call(
+                    closure_new,
+                    &[environment_type.size_of().unwrap().into(), drop.into()],
+                    "closure.env",
+                )
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value();
+
+            for (i, (name, entry)) in captures.iter().enumerate() {
+                let value = compiler.builder.build_load(
+                    entry.ty.to_llvm_type(compiler.context),
+                    entry.pointer,
+                    format!("load.{name}").as_str(),
+                );
+                let ptr = compiler
+                    .builder
+                    .build_struct_gep(
+                        environment_type,
+                        environment,
+                        i as u32,
+                        format!("closure.env.{name}").as_str(),
+                    )
+                    .unwrap();
+                compiler.retain_value(&Value::new(value, entry.ty.clone()));
+                compiler.builder.build_store(ptr, value);
+            }
+
+            environment
+        };
+
+        let code = compiler.detached(|compiler| {
+            self.codegen_code(compiler, &function_type, &captures, environment_type)
+        })?;
+
+        let closure_type = CodegenType::Closure(function_type);
+        let mut closure = closure_type
+            .to_llvm_type(compiler.context)
+            .into_struct_type()
+            .get_undef()
+            .as_aggregate_value_enum();
+        closure = compiler
+            .builder
+            .build_insert_value(
+                closure,
+                code.as_global_value().as_pointer_value(),
+                0,
+                "closure.code",
+            )
+            .unwrap();
+        closure = compiler
+            .builder
+            .build_insert_value(closure, environment, 1, "closure.env")
+            .unwrap();
+
+        // Like a string returned by a call, the closure comes with a reference of its own.
+        let closure = Value::new(closure.as_basic_value_enum(), closure_type);
+        compiler.register_temporary(&closure);
+
+        Ok(closure)
+    }
+}
+
+impl LambdaExpression {
+    // Called by the runtime to release the values held by an environment before freeing it.
+    fn codegen_drop<'a>(
+        compiler: &mut Compiler<'a>,
+        captures: &BTreeMap<String, VariableEntry<'a>>,
+        environment_type: types::StructType<'a>,
+    ) -> CompileResult<FunctionValue<'a>> {
+        let i8_ptr_type = compiler.context.i8_type().ptr_type(AddressSpace::from(0));
+        let function = compiler.module.add_function(
+            "closure.drop",
+            compiler
+                .context
+                .void_type()
+                .fn_type(&[i8_ptr_type.into()], false),
+            Some(Linkage::Private),
+        );
+
+        compiler.current_function = Some(CurrentFunction {
+            function,
+            return_type: CodegenType::Void,
+        });
+
+        let basic_block = compiler.context.append_basic_block(function, "entry");
+        compiler.builder.position_at_end(basic_block);
+
+        let environment = function.get_nth_param(0).unwrap().into_pointer_value();
+        for (i, (name, entry)) in captures.iter().enumerate() {
+            let ptr = compiler
+                .builder
+                .build_struct_gep(
+                    environment_type,
+                    environment,
+                    i as u32,
+                    format!("closure.env.{name}").as_str(),
+                )
+                .unwrap();
+            let value = compiler.builder.build_load(
+                entry.ty.to_llvm_type(compiler.context),
+                ptr,
+                format!("load.{name}").as_str(),
+            );
+            compiler.release_value(&Value::new(value, entry.ty.clone()));
+        }
+
+        compiler.builder.build_return(None);
+
+        Ok(function)
+    }
+
+    fn codegen_code<'a>(
+        &self,
+        compiler: &mut Compiler<'a>,
+        function_type: &FunctionType,
+        captures: &BTreeMap<String, VariableEntry<'a>>,
+        environment_type: types::StructType<'a>,
+    ) -> CompileResult<FunctionValue<'a>> {
+        let return_type = *function_type.return_type.clone();
+        let function = compiler.module.add_function(
+            "closure",
+            function_type
+                .with_environment()
+                .to_llvm_function_type(compiler.context),
+            None,
+        );
+
+        compiler.current_function = Some(CurrentFunction {
+            function,
+            return_type: return_type.clone(),
+        });
+
+        let basic_block = compiler.context.append_basic_block(function, "entry");
+        compiler.builder.position_at_end(basic_block);
+
+        // Captured variables live in the environment, so that assignments to them are seen by
+        // later calls of the same closure.
+        compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+
+        let environment = function.get_nth_param(0).unwrap().into_pointer_value();
+        for (i, (name, entry)) in captures.iter().enumerate() {
+            let ptr = compiler
+                .builder
+                .build_struct_gep(
+                    environment_type,
+                    environment,
+                    i as u32,
+                    format!("closure.env.{name}").as_str(),
+                )
+                .unwrap();
+
+            compiler.symbol_table.insert_variable(
+                name.clone(),
+                entry.ty.clone(),
+                ptr,
+                self.span,
+            )?;
+        }
+
+        compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+        compiler.enter_scope();
+
+        for (i, parameter) in function.get_param_iter().skip(1).enumerate() {
+            let parameter_name = self.parameters[i].name.clone();
+            let alloca = compiler.builder.build_alloca(
+                function_type.parameters[i].to_llvm_type(compiler.context),
+                format!("arg.{}", parameter_name.identifier).as_str(),
+            );
+            compiler.builder.build_store(alloca, parameter);
+            compiler.own_value(
+                &Value::new(parameter, function_type.parameters[i].clone()),
+                alloca,
+            );
+
+            compiler.symbol_table.insert_variable(
+                parameter_name.identifier.clone(),
+                function_type.parameters[i].clone(),
+                alloca,
+                parameter_name.span,
+            )?;
+        }
+
+        self.body.codegen(compiler)?;
+        compiler.exit_scope();
+
+        if return_type != CodegenType::Void && compiler.current_return.is_none() {
+            return Err(CompileError::expected("return", self.span));
+        }
+
+        if return_type == CodegenType::Void
+            && compiler
+                .builder
+                .get_insert_block()
+                .unwrap()
+                .get_terminator()
+                .is_none()
+        {
+            compiler.builder.build_return(None);
+        }
+
+        Ok(function)
+    }
+}
+
+impl DisplayNode for LambdaExpression {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| format!("{} {}", parameter.name.identifier, parameter.ty.kind))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "({}) -> {} =>",
+            parameters.join(", "),
+            self.return_type.kind
+        )?;
+
+        self.body.display(f, indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::{
+            testing::{
+                calls, compile, compile_error, compile_with, function, instructions,
+                operand_instruction, reference_balances,
+            },
+            CompileErrorKind,
+        },
+        CompileOptions,
+    };
+    use inkwell::{context::Context, values::InstructionOpcode, IntPredicate};
+
+    #[test]
+    fn index_bounds_check() {
+        let source = "define f(numbers int[], i int) -> int = numbers[i]";
+
+        // A single unsigned comparison catches negative indices too.
+        let context = Context::create();
+        let module = compile(&context, source);
+        let f = function(&module, "f");
+        let panics = calls(f, "panic_index_out_of_bounds");
+        assert_eq!(panics.len(), 1);
+
+        let check = f
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .find(|instruction| {
+                instruction.get_opcode() == InstructionOpcode::Br
+                    && instruction.get_num_operands() == 3
+            })
+            .unwrap();
+        let compare = operand_instruction(check, 0).unwrap();
+        assert_eq!(compare.get_icmp_predicate(), Some(IntPredicate::ULT));
+        let failure = check.get_operand(1).unwrap().right().unwrap();
+        assert_eq!(Some(failure), panics[0].get_parent());
+
+        let module = compile_with(
+            &context,
+            source,
+            CompileOptions {
+                bounds_checks: false,
+                ..Default::default()
+            },
+        );
+        let f = function(&module, "f");
+        assert!(calls(f, "panic_index_out_of_bounds").is_empty());
+        assert!(!f
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .any(|instruction| instruction.get_icmp_predicate() == Some(IntPredicate::ULT)));
+    }
+
+    #[test]
+    fn slice_range_check() {
+        let source = "define f(numbers int[], start int) -> int = len(numbers[start..])";
+
+        let context = Context::create();
+        let module = compile(&context, source);
+        assert_eq!(
+            calls(function(&module, "f"), "panic_slice_out_of_range").len(),
+            1
+        );
+
+        let module = compile_with(
+            &context,
+            source,
+            CompileOptions {
+                bounds_checks: false,
+                ..Default::default()
+            },
+        );
+        assert!(calls(function(&module, "f"), "panic_slice_out_of_range").is_empty());
+    }
+
+    #[test]
+    fn new_array_length_check() {
+        let source =
+            "define f(n int) -> int =\n    let numbers = new int[n]\n    return len(numbers)";
+
+        // The check is kept without bounds checks, as a negative length would wrap the size.
+        let context = Context::create();
+        let module = compile_with(
+            &context,
+            source,
+            CompileOptions {
+                bounds_checks: false,
+                ..Default::default()
+            },
+        );
+        let f = function(&module, "f");
+        let panics = calls(f, "panic_negative_length");
+        assert_eq!(panics.len(), 1);
+
+        let check = f
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .find(|instruction| {
+                instruction.get_opcode() == InstructionOpcode::Br
+                    && instruction.get_num_operands() == 3
+            })
+            .unwrap();
+        let compare = operand_instruction(check, 0).unwrap();
+        assert_eq!(compare.get_icmp_predicate(), Some(IntPredicate::SGE));
+        let failure = check.get_operand(1).unwrap().right().unwrap();
+        assert_eq!(Some(failure), panics[0].get_parent());
+
+        // The length is checked before it is allocated.
+        let allocate = calls(f, "allocate");
+        assert_eq!(allocate.len(), 1);
+        assert_ne!(allocate[0].get_parent(), check.get_parent());
+    }
+
+    #[test]
+    fn release_replaced_value() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "define reset(name str*) -> int =\n    *name = \"\"\n    return 0",
+        );
+        let reset = function(&module, "reset");
+        assert_eq!(calls(reset, "str_retain").len(), 1);
+        assert_eq!(calls(reset, "str_release").len(), 1);
+
+        // Only the variants holding strings release them.
+        let module = compile(&context, "enum Shape\n    | Circle(name str)\n    | Square(side int)\n    | Empty\n\ndefine reset(shape Shape*) -> int =\n    *shape = Shape::Empty\n    return 0");
+        let reset = function(&module, "reset");
+        let switch = reset
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .find(|instruction| instruction.get_opcode() == InstructionOpcode::Switch)
+            .unwrap();
+        assert_eq!(calls(reset, "str_release").len(), 1);
+        assert!(switch.get_num_operands() >= 4);
+    }
+
+    #[test]
+    fn release_on_reassignment() {
+        let context = Context::create();
+        let module = compile(&context, "define f(name str) -> int =\n    let copy = name\n    copy = \"other\"\n    copy = name\n    return 0");
+
+        let f = function(&module, "f");
+        assert!(calls(f, "str_release").len() >= 4);
+        assert!(reference_balances(f).iter().all(|balance| *balance == 0));
+    }
+
+    #[test]
+    fn closure_environment() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "define greet(name str) -> () -> int = () -> int => len(name)",
+        );
+
+        // The environment holds a copy of the captured string, released by its drop function.
+        let closure_new = calls(function(&module, "greet"), "closure_new");
+        assert_eq!(closure_new.len(), 1);
+        let drop = closure_new[0]
+            .get_operand(1)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let drop = function(&module, drop.get_name().to_str().unwrap());
+        assert_eq!(calls(drop, "str_release").len(), 1);
+    }
+
+    #[test]
+    fn builtin_len() {
+        let context = Context::create();
+        let module = compile(&context, "define f(x str) -> int = len(x)");
+        assert!(module.get_function("len").is_none());
+
+        // A function named `len` is called instead.
+        let module = compile(
+            &context,
+            "define len(x int) -> int = x * 2\n\ndefine f(x int) -> int = len(x)",
+        );
+        assert_eq!(calls(function(&module, "f"), "len").len(), 1);
+    }
+
+    #[test]
+    fn unknown_function_before_arguments() {
+        assert!(matches!(
+            compile_error("define f -> int = missing(undefined)"),
+            CompileErrorKind::FunctionNotFound(_)
+        ));
+        assert!(matches!(
+            compile_error(
+                "define max<T>(a T, b T) -> T = a\n\ndefine f -> int = max(1, undefined)"
+            ),
+            CompileErrorKind::IdentifierNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn shift_amount_wraps() {
+        let context = Context::create();
+        let module = compile(&context, "define f(x u8, n u8) -> u8 = x << n");
+
+        let shift = function(&module, "f")
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .find(|instruction| instruction.get_opcode() == InstructionOpcode::Shl)
+            .unwrap();
+        let amount = operand_instruction(shift, 1).unwrap();
+        assert_eq!(amount.get_opcode(), InstructionOpcode::And);
+        let mask = amount
+            .get_operand(1)
+            .unwrap()
+            .left()
+            .unwrap()
+            .into_int_value();
+        assert_eq!(mask.get_zero_extended_constant(), Some(7));
+    }
+
+    #[test]
+    fn wildcard_after_every_variant() {
+        let source = "enum Light\n    | Red\n    | Green\n\ndefine f(light Light) -> int =\n    return match light\n        Red => 1\n        Green => 2\n        _ => 3";
+        assert!(matches!(
+            compile_error(source),
+            CompileErrorKind::UnreachablePattern
+        ));
+    }
+}
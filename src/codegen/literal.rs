@@ -1,12 +1,14 @@
-use super::{types::ArrayType, CompileError, CompileResult, Expression};
-use crate::{
-    display, CodegenType, Compiler, DisplayNode, ExpressionCodegen, Span, StructType, Value,
+use super::{
+    types::{resolve_type_arguments, ArrayType, AstType, AstTypeKind, IntegerType},
+    CompileError, CompileResult, Expression,
 };
+use crate::{display, CodegenType, Compiler, DisplayNode, ExpressionCodegen, Span, Value};
+use indexmap::IndexMap;
 use inkwell::{
     types::BasicType,
-    values::{BasicValue, BasicValueEnum},
+    values::{AggregateValue, BasicValue, BasicValueEnum},
 };
-use std::{collections::BTreeMap, fmt};
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Literal {
@@ -17,6 +19,8 @@ pub enum Literal {
     String(StringLiteral),
     Array(ArrayLiteral),
     Struct(StructLiteral),
+    Enum(EnumLiteral),
+    Tuple(TupleLiteral),
 }
 
 impl ExpressionCodegen for Literal {
@@ -30,7 +34,7 @@ impl ExpressionCodegen for Literal {
                 }
             };
         }
-        inner! { Identifier Int Float Boolean String Array Struct }
+        inner! { Identifier Int Float Boolean String Array Struct Enum Tuple }
     }
 }
 
@@ -46,7 +50,7 @@ impl From<Literal> for Span {
             };
         }
 
-        inner! { Identifier Int Float Boolean String Array Struct }
+        inner! { Identifier Int Float Boolean String Array Struct Enum Tuple }
     }
 }
 
@@ -62,7 +66,7 @@ impl DisplayNode for Literal {
             };
         }
 
-        inner! { Identifier Int Float Boolean String Array Struct }
+        inner! { Identifier Int Float Boolean String Array Struct Enum Tuple }
     }
 }
 
@@ -78,10 +82,24 @@ impl ExpressionCodegen for Identifier {
         let entry = match _symbol_table.get_variable(&self.identifier) {
             Some(entry) => entry,
             None => {
+                if let Some(constant) = _symbol_table.get_constant(&self.identifier) {
+                    return Ok(constant.value.codegen(compiler));
+                }
+
+                // A named function evaluates to a pointer to it.
+                if let Some(entry) = _symbol_table.get_function(&self.identifier) {
+                    if let Some(function) = compiler.module.get_function(&entry.name) {
+                        return Ok(Value::new(
+                            function.as_global_value().as_pointer_value().into(),
+                            CodegenType::Function(entry.function_type),
+                        ));
+                    }
+                }
+
                 return Err(CompileError::identifier_not_found(
                     self.identifier.clone(),
                     self.span,
-                ))
+                ));
             }
         };
 
@@ -105,46 +123,81 @@ impl DisplayNode for Identifier {
 #[derive(Debug, Clone)]
 pub struct IntLiteral {
     pub value: i64,
+    pub ty: Option<IntegerType>,
     pub span: Span,
 }
 
+impl IntLiteral {
+    pub fn ty(&self) -> CodegenType {
+        match self.ty {
+            Some(ty) => ty.to_codegen_type(),
+            None => CodegenType::Int,
+        }
+    }
+}
+
 impl ExpressionCodegen for IntLiteral {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let ty = self.ty();
+
         Ok(Value::new(
-            compiler
-                .context
-                .i64_type()
+            ty.to_llvm_type(compiler.context)
+                .into_int_type()
                 .const_int(self.value as u64, false)
                 .into(),
-            CodegenType::Int,
+            ty,
         ))
     }
 }
 
 impl DisplayNode for IntLiteral {
     fn display(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
-        write!(f, "{}", self.value)
+        match self.ty {
+            Some(ty) if !ty.signed => write!(f, "{}{ty}", self.value as u64),
+            Some(ty) => write!(f, "{}{ty}", self.value),
+            None => write!(f, "{}", self.value),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct FloatLiteral {
     pub value: f64,
+    pub single_precision: bool,
     pub span: Span,
 }
 
+impl FloatLiteral {
+    pub fn ty(&self) -> CodegenType {
+        if self.single_precision {
+            CodegenType::F32
+        } else {
+            CodegenType::Float
+        }
+    }
+}
+
 impl ExpressionCodegen for FloatLiteral {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let ty = self.ty();
+
         Ok(Value::new(
-            compiler.context.f64_type().const_float(self.value).into(),
-            CodegenType::Float,
+            ty.to_llvm_type(compiler.context)
+                .into_float_type()
+                .const_float(self.value)
+                .into(),
+            ty,
         ))
     }
 }
 
 impl DisplayNode for FloatLiteral {
     fn display(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
-        write!(f, "{}", self.value)
+        if self.single_precision {
+            write!(f, "{}f32", self.value)
+        } else {
+            write!(f, "{}", self.value)
+        }
     }
 }
 
@@ -181,9 +234,7 @@ pub struct StringLiteral {
 
 impl ExpressionCodegen for StringLiteral {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        let string = compiler
-            .builder
-            .build_global_string_ptr(self.value.as_str(), ".str");
+        let string = compiler.build_string(self.value.as_str());
 
         Ok(Value::new(
             string.as_basic_value_enum(),
@@ -211,6 +262,7 @@ impl ExpressionCodegen for ArrayLiteral {
 
         for val in self.elements.clone() {
             let value = val.codegen(compiler)?;
+            compiler.retain_value(&value);
             values.push(value.llvm_value);
 
             match element_type.clone() {
@@ -256,14 +308,21 @@ impl ExpressionCodegen for ArrayLiteral {
             compiler.builder.build_store(ptr, *val);
         }
 
-        Ok(Value::new(
-            ptr.as_basic_value_enum(),
-            CodegenType::Array(ArrayType {
+        let array = compiler.build_array(
+            ptr,
+            compiler
+                .context
+                .i64_type()
+                .const_int(values.len() as u64, false),
+            ArrayType {
                 ty: Box::new(array_type),
                 len: Some(values.len()),
                 span: self.span,
-            }),
-        ))
+            },
+        );
+        compiler.own_elements(&array);
+
+        Ok(array)
     }
 }
 
@@ -280,43 +339,142 @@ impl DisplayNode for ArrayLiteral {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct TupleLiteral {
+    pub elements: Vec<Expression>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for TupleLiteral {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let mut values = Vec::new();
+        for element in self.elements.iter() {
+            values.push(element.codegen(compiler)?);
+        }
+
+        let ty = CodegenType::Tuple(values.iter().map(|value| value.ty.clone()).collect());
+
+        let mut aggregate = ty
+            .to_llvm_type(compiler.context)
+            .into_struct_type()
+            .get_undef()
+            .as_aggregate_value_enum();
+
+        for (i, value) in values.into_iter().enumerate() {
+            aggregate = compiler
+                .builder
+                .build_insert_value(
+                    aggregate,
+                    value.llvm_value,
+                    i as u32,
+                    format!("tuple.{i}").as_str(),
+                )
+                .unwrap();
+        }
+
+        Ok(Value::new(aggregate.as_basic_value_enum(), ty))
+    }
+}
+
+impl DisplayNode for TupleLiteral {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, element) in self.elements.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            element.display(f, indent)?;
+        }
+        if self.elements.len() == 1 {
+            write!(f, ",")?;
+        }
+        write!(f, ")")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StructLiteral {
     pub name: Identifier,
-    pub fields: BTreeMap<String, Expression>,
+    pub type_arguments: Vec<AstType>,
+    pub fields: IndexMap<String, Expression>,
     pub span: Span,
 }
 
 impl ExpressionCodegen for StructLiteral {
     fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
-        let _symbol_table = compiler.symbol_table.clone();
-        let entry = match _symbol_table.get_struct(&self.name.identifier) {
-            Some(entry) => entry,
-            None => {
-                return Err(CompileError::struct_not_found(
-                    self.name.identifier.clone(),
-                    self.span,
-                ))
+        let mut field_values = Vec::new();
+
+        for val in self.fields.iter() {
+            field_values.push(val.1.codegen(compiler)?);
+        }
+
+        let struct_type = if !self.type_arguments.is_empty() {
+            match AstTypeKind::Generic(self.name.clone(), self.type_arguments.clone())
+                .to_codegen_type(&compiler.symbol_table)?
+            {
+                CodegenType::Struct(struct_type) => struct_type,
+                _ => {
+                    return Err(CompileError::struct_not_found(
+                        self.name.identifier.clone(),
+                        self.span,
+                    ))
+                }
+            }
+        } else {
+            match compiler.symbol_table.get_struct(&self.name.identifier) {
+                Some(entry) => entry.struct_type,
+                // The type arguments of a generic struct are inferred from its field values.
+                None => match compiler
+                    .symbol_table
+                    .get_generic_struct(&self.name.identifier)
+                {
+                    Some(entry) => {
+                        let mut type_arguments = entry
+                            .declaration
+                            .type_parameters
+                            .iter()
+                            .map(|parameter| (parameter.identifier.clone(), None))
+                            .collect::<IndexMap<_, _>>();
+
+                        for (val, value) in self.fields.iter().zip(field_values.iter()) {
+                            if let Some(ty) = entry.declaration.fields.get(val.0) {
+                                ty.kind.infer_type_arguments(
+                                    &value.ty,
+                                    &mut type_arguments,
+                                    val.1.clone().into(),
+                                )?;
+                            }
+                        }
+
+                        entry.instantiate(
+                            &compiler.symbol_table,
+                            resolve_type_arguments(type_arguments, self.span)?,
+                            self.span,
+                        )?
+                    }
+                    None => {
+                        return Err(CompileError::struct_not_found(
+                            self.name.identifier.clone(),
+                            self.span,
+                        ))
+                    }
+                },
             }
         };
 
-        if self.fields.len() != entry.struct_type.fields.len() {
+        if self.fields.len() != struct_type.fields.len() {
             return Err(CompileError::wrong_number_of_fields(
-                entry.struct_type.fields.len(),
+                struct_type.fields.len(),
                 self.fields.len(),
                 self.span,
             ));
         }
 
-        let mut values: Vec<BasicValueEnum> = Vec::new();
-        let mut feilds_type = BTreeMap::new();
+        let mut values: Vec<(usize, BasicValueEnum)> = Vec::new();
 
-        for (i, val) in self.fields.iter().enumerate() {
-            let value = val.1.codegen(compiler)?;
-            values.push(value.llvm_value);
-
-            let field_type = match entry.struct_type.fields.get(val.0) {
-                Some((_, ty)) => ty.clone(),
+        for (val, value) in self.fields.iter().zip(field_values) {
+            let (index, field_type) = match struct_type.fields.get(val.0) {
+                Some((index, ty)) => (*index, ty.clone()),
                 None => return Err(CompileError::field_not_found(val.0.clone(), self.span)),
             };
 
@@ -328,40 +486,46 @@ impl ExpressionCodegen for StructLiteral {
                 ));
             }
 
-            feilds_type.insert(val.0.clone(), (i, value.ty));
+            values.push((index, value.llvm_value));
         }
 
-        let ptr = compiler.builder.build_alloca(
-            entry.ty,
-            format!("struct.{}", self.name.identifier).as_str(),
-        );
-
-        for (i, val) in values.iter().enumerate() {
-            let field = unsafe {
-                compiler.builder.build_in_bounds_gep(
-                    compiler.context.i64_type(),
-                    ptr,
-                    &[compiler.context.i64_type().const_int(i as u64, false)],
-                    format!("ptr.struct.{}.{i}", self.name.identifier).as_str(),
+        // Structs are first-class values, copied on `let`, assignment, calls and returns.
+        let mut aggregate = struct_type
+            .to_llvm_struct_type(compiler.context)
+            .get_undef()
+            .as_aggregate_value_enum();
+
+        for (i, val) in values {
+            aggregate = compiler
+                .builder
+                .build_insert_value(
+                    aggregate,
+                    val,
+                    i as u32,
+                    format!("struct.{}.{i}", self.name.identifier).as_str(),
                 )
-            };
-            compiler.builder.build_store(field, *val);
+                .unwrap();
         }
 
         Ok(Value::new(
-            ptr.as_basic_value_enum(),
-            CodegenType::Struct(StructType {
-                name: self.name.identifier.clone(),
-                fields: feilds_type,
-                span: self.span,
-            }),
+            aggregate.as_basic_value_enum(),
+            CodegenType::Struct(struct_type),
         ))
     }
 }
 
 impl DisplayNode for StructLiteral {
     fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
-        write!(f, "{} {{", self.name.identifier)?;
+        write!(f, "{}", self.name.identifier)?;
+        if !self.type_arguments.is_empty() {
+            let type_arguments = self
+                .type_arguments
+                .iter()
+                .map(|argument| argument.kind.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "<{}>", type_arguments.join(", "))?;
+        }
+        write!(f, " {{")?;
         for (i, val) in self.fields.iter().enumerate() {
             writeln!(f)?;
             display::indent(f, indent + 1)?;
@@ -376,3 +540,161 @@ impl DisplayNode for StructLiteral {
         write!(f, "}}")
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct EnumLiteral {
+    pub name: Identifier,
+    pub variant: Identifier,
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+impl ExpressionCodegen for EnumLiteral {
+    fn codegen<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<Value<'a>> {
+        let enum_type = match compiler.symbol_table.get_enum(&self.name.identifier) {
+            Some(entry) => entry.enum_type,
+            None => {
+                return Err(CompileError::type_not_found(
+                    self.name.identifier.clone(),
+                    self.name.span,
+                ))
+            }
+        };
+
+        let (tag, fields) = match enum_type.variants.get_full(&self.variant.identifier) {
+            Some((tag, _, fields)) => (tag, fields.clone()),
+            None => {
+                return Err(CompileError::variant_not_found(
+                    self.variant.identifier.clone(),
+                    self.variant.span,
+                ))
+            }
+        };
+
+        if self.arguments.len() != fields.len() {
+            return Err(CompileError::wrong_number_of_fields(
+                fields.len(),
+                self.arguments.len(),
+                self.span,
+            ));
+        }
+
+        let enum_llvm_type = enum_type.to_llvm_struct_type(compiler.context);
+        let variant_llvm_type =
+            enum_type.variant_llvm_struct_type(compiler.context, &self.variant.identifier);
+
+        let alloca = compiler.builder.build_alloca(
+            enum_llvm_type,
+            format!("enum.{}", self.name.identifier).as_str(),
+        );
+
+        let tag_ptr = compiler
+            .builder
+            .build_struct_gep(enum_llvm_type, alloca, 0, "enum.tag")
+            .unwrap();
+        compiler.builder.build_store(
+            tag_ptr,
+            compiler.context.i32_type().const_int(tag as u64, false),
+        );
+
+        let payload_ptr = compiler
+            .builder
+            .build_struct_gep(enum_llvm_type, alloca, 1, "enum.payload")
+            .unwrap();
+
+        for (i, (argument, ty)) in self.arguments.iter().zip(fields.values()).enumerate() {
+            let value = argument.codegen(compiler)?;
+
+            if *ty != value.ty {
+                return Err(CompileError::type_mismatch(
+                    ty.clone(),
+                    value.ty,
+                    argument.clone().into(),
+                ));
+            }
+
+            let field_ptr = compiler
+                .builder
+                .build_struct_gep(
+                    variant_llvm_type,
+                    payload_ptr,
+                    i as u32,
+                    format!("enum.{}.{i}", self.variant.identifier).as_str(),
+                )
+                .unwrap();
+            compiler.builder.build_store(field_ptr, value.llvm_value);
+        }
+
+        Ok(Value::new(
+            compiler.builder.build_load(
+                enum_llvm_type,
+                alloca,
+                format!("load.enum.{}", self.name.identifier).as_str(),
+            ),
+            CodegenType::Enum(enum_type),
+        ))
+    }
+}
+
+impl DisplayNode for EnumLiteral {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(f, "{}::{}", self.name.identifier, self.variant.identifier)?;
+        if !self.arguments.is_empty() {
+            write!(f, "(")?;
+            for (i, argument) in self.arguments.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                argument.display(f, indent)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{
+        testing::{
+            block_named, calls, calls_in_block, compile, compile_error, function,
+            reference_balances,
+        },
+        CompileErrorKind,
+    };
+    use inkwell::context::Context;
+
+    #[test]
+    fn new_generic_struct_literal() {
+        let context = Context::create();
+        compile(&context, "struct Pair<T, U>\n    | first T\n    | second U\n\ndefine f -> Pair<int, bool>* = new Pair<int, bool> { first: 1, second: true }");
+
+        assert!(matches!(
+            compile_error("struct Pair<T, U>\n    | first T\n    | second U\n\ndefine f -> int =\n    let pair = new Pair<int> { first: 1, second: 2 }\n    return 0"),
+            CompileErrorKind::WrongNumberOfTypeArguments(..)
+        ));
+    }
+
+    #[test]
+    fn release_string_fields() {
+        let context = Context::create();
+        let module = compile(&context, "struct Person\n    | name str\n    | age int\n\ndefine f(name str) -> int =\n    let person = Person { name: name, age: 1 }\n    let other = person\n    other = Person { name: \"\", age: 2 }\n    return other.age");
+
+        // Copies of the struct retain its string, which is released along with each of them.
+        let f = function(&module, "f");
+        assert!(calls(f, "str_retain").len() >= 3);
+        assert!(reference_balances(f).iter().all(|balance| *balance == 0));
+    }
+
+    #[test]
+    fn array_literal_owns_elements() {
+        let context = Context::create();
+        let module = compile(&context, "define make(n int) -> () -> int = () -> int => n\n\ndefine f -> int =\n    let makers = [make(1), make(2)]\n    return makers[0]()");
+
+        // Each element keeps a reference of its own, released in a loop once the scope is exited.
+        let f = function(&module, "f");
+        assert!(calls(f, "closure_retain").len() >= 2);
+        let release = block_named(f, "release.body");
+        assert_eq!(calls_in_block(release, "closure_release").len(), 1);
+    }
+}
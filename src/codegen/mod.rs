@@ -6,9 +6,14 @@ pub use expression::*;
 pub use literal::*;
 pub use statement::*;
 
+pub mod abi;
+pub mod constant;
 pub mod symbol_table;
 pub mod types;
 
+#[cfg(test)]
+mod testing;
+
 use crate::{
     lexer::LexingError,
     parser::{ParsingError, ParsingErrorKind},
@@ -84,19 +89,34 @@ impl_error_kind! {
     VariableAlreadyDeclared(identifier: String): variable_already_declared<T: ToString>(T) => "variable `{identifier}` already declared",
     FunctionAlreadyDeclared(identifier: String): function_already_declared<T: ToString>(T) => "function `{identifier}` already declared",
     StructAlreadyDeclared(identifier: String): struct_already_declared<T: ToString>(T) => "struct `{identifier}` already declared",
+    EnumAlreadyDeclared(identifier: String): enum_already_declared<T: ToString>(T) => "enum `{identifier}` already declared",
     TypeAlreadyDeclared(identifier: String): type_already_declared<T: ToString>(T) => "type `{identifier}` already declared",
     IdentifierNotFound(identifier: String): identifier_not_found<T: ToString>(T) => "identifier `{identifier}` not found",
     StructNotFound(name: String): struct_not_found<T: ToString>(T) => "struct `{name}` not found",
     FieldNotFound(name: String): field_not_found<T: ToString>(T) => "field `{name}` not found",
+    VariantNotFound(name: String): variant_not_found<T: ToString>(T) => "variant `{name}` not found",
     FunctionNotFound(name: String): function_not_found<T: ToString>(T) => "function `{name}` not found",
     TypeNotFound(name: String): type_not_found<T: ToString>(T) => "type `{name}` not found",
+    GenericImpl(name: String): generic_impl<T: ToString>(T) => "methods cannot be implemented for generic struct `{name}`",
     UnknownSize: unknown_size => "unknown size",
     WrongNumberOfArguments(expected: String, found: String): wrong_number_of_arguments(usize, usize) => "wrong number of arguments: expected `{expected}`, found `{found}`",
     WrongNumberOfFields(expected: String, found: String): wrong_number_of_fields(usize, usize) => "wrong number of fields: expected `{expected}`, found `{found}`",
     CallNonFunctionType: call_non_function_type => "call non-function type",
     MemberAccessNonStructType: member_access_non_struct_type => "member access non-struct type",
     CannotBeAssigned: cannot_be_assigned => "cannot be assigned",
-    ElseClauseIsRequired: else_clause_is_required => "else clause is required"
+    ElseClauseIsRequired: else_clause_is_required => "else clause is required",
+    OutsideOfLoop(keyword: String): outside_of_loop<T: ToString>(T) => "`{keyword}` outside of loop",
+    NotConstant: not_constant => "expression is not a compile-time constant",
+    DivisionByZero: division_by_zero => "division by zero",
+    ShiftOutOfRange(amount: String): shift_out_of_range<T: ToString>(T) => "shift amount `{amount}` out of range",
+    NonExhaustivePatterns(patterns: String): non_exhaustive_patterns<T: ToString>(T) => "non-exhaustive patterns: {patterns} not covered",
+    UnreachablePattern: unreachable_pattern => "unreachable pattern",
+    CannotInferTypeParameter(name: String): cannot_infer_type_parameter<T: ToString>(T) => "cannot infer type parameter `{name}`",
+    WrongNumberOfTypeArguments(expected: String, found: String): wrong_number_of_type_arguments(usize, usize) => "wrong number of type arguments: expected `{expected}`, found `{found}`",
+    UnsupportedTarget(triple: String): unsupported_target<T: ToString>(T) => "unsupported target `{triple}`",
+    StructByValueInVariadicFunction(name: String): struct_by_value_in_variadic_function<T: ToString>(T) => "variadic external function `{name}` cannot take or return structs by value",
+    StringReturnedByVariadicFunction(name: String): string_returned_by_variadic_function<T: ToString>(T) => "variadic external function `{name}` cannot return `str`, return `i8*` and convert it with `as str` instead",
+    UnsupportedReturnType(ty: String): unsupported_return_type<T: ToString>(T) => "external functions cannot return `{ty}`, which holds strings or closures"
 }
 
 pub type CompileResult<T> = Result<T, CompileError>;
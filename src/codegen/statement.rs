@@ -1,25 +1,38 @@
 use super::{
-    symbol_table::SymbolTable, types::AstType, CompileError, CompileResult, Expression, Identifier,
+    abi,
+    constant::{self, Constant},
+    symbol_table::SymbolTable,
+    types::{AstType, AstTypeKind, EnumType},
+    CompileError, CompileResult, Expression, Identifier, MatchExpression,
 };
 use crate::{
     display, CodegenType, Compiler, CurrentFunction, DisplayNode, ExpressionCodegen, FunctionType,
-    Span, StatementCodegen, StructType,
+    LoopTarget, Span, StatementCodegen, StructType, Value,
 };
-use inkwell::{types::BasicType, IntPredicate};
-use std::{collections::BTreeMap, fmt};
+use indexmap::IndexMap;
+use inkwell::{types::BasicType, values::FunctionValue, AddressSpace, IntPredicate};
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     Expression(Expression),
     Let(LetStatement),
+    Const(ConstStatement),
     Function(FunctionDefinition),
     ExternalFunction(ExternalFunctionDeclaration),
     Struct(StructDeclaration),
+    Enum(EnumDeclaration),
+    Impl(ImplBlock),
+    Match(MatchExpression),
     Return(ReturnStatement),
     If(IfStatement),
     Type(TypeDeclaration),
     While(While),
     For(For),
+    Foreach(Foreach),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
+    Delete(DeleteStatement),
     Ellipsis,
 }
 
@@ -31,15 +44,20 @@ impl StatementCodegen for Statement {
                     $(
                         Statement::$ident(statement) => { statement.codegen(compiler)?; },
                     )*
+                    Statement::Match(statement) => { statement.codegen_match(compiler, false)?; },
                     Statement::Ellipsis => {},
                 }
             };
         }
 
+        compiler.enter_statement();
+
         inner! {
-            Expression Let Function ExternalFunction Struct Return If Type While For
+            Expression Let Const Function ExternalFunction Struct Enum Impl Return If Type While For Foreach Break Continue Delete
         }
 
+        compiler.exit_statement();
+
         Ok(())
     }
 }
@@ -56,6 +74,10 @@ impl DisplayNode for Statement {
                         display::indent(f, indent)?;
                         expression.display(f, indent)?;
                     },
+                    Statement::Match(expression) => {
+                        display::indent(f, indent)?;
+                        expression.display(f, indent)?;
+                    },
                     Statement::Ellipsis => {
                         display::indent(f, indent)?;
                         writeln!(f, "...")?;
@@ -65,7 +87,7 @@ impl DisplayNode for Statement {
         }
 
         inner! {
-            Let Function ExternalFunction Struct Return If Type While For
+            Let Const Function ExternalFunction Struct Enum Impl Return If Type While For Foreach Break Continue Delete
         }
 
         writeln!(f)
@@ -74,12 +96,19 @@ impl DisplayNode for Statement {
 
 #[derive(Debug, Clone)]
 pub struct LetStatement {
-    pub name: Identifier,
+    pub binding: LetBinding,
     pub ty: Option<AstType>,
     pub value: Expression,
     pub span: Span,
 }
 
+#[derive(Debug, Clone)]
+pub enum LetBinding {
+    Identifier(Identifier),
+    // `let (a, b) = pair` binds each element of a tuple, skipping those named `_`.
+    Tuple(Vec<Identifier>),
+}
+
 impl StatementCodegen for LetStatement {
     fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
         let value = self.value.codegen(compiler)?;
@@ -94,18 +123,83 @@ impl StatementCodegen for LetStatement {
             }
         }
 
-        let alloca = compiler.builder.build_alloca(
-            value.ty.to_llvm_type(compiler.context),
-            &self.name.identifier,
-        );
+        let names = match &self.binding {
+            LetBinding::Identifier(name) => return Self::bind(compiler, name, value),
+            LetBinding::Tuple(names) => names,
+        };
+
+        let elements = match value.ty {
+            CodegenType::Tuple(ref elements) => elements.clone(),
+            _ => return Err(CompileError::expected("tuple", self.value.clone().into())),
+        };
+
+        if names.len() != elements.len() {
+            return Err(CompileError::wrong_number_of_fields(
+                elements.len(),
+                names.len(),
+                self.span,
+            ));
+        }
+
+        for (i, (name, ty)) in names.iter().zip(elements).enumerate() {
+            if name.identifier == "_" {
+                continue;
+            }
+
+            let element = compiler
+                .builder
+                .build_extract_value(
+                    value.llvm_value.into_struct_value(),
+                    i as u32,
+                    format!("tuple.{i}").as_str(),
+                )
+                .unwrap();
+
+            Self::bind(compiler, name, Value::new(element, ty))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl LetStatement {
+    fn bind<'a>(
+        compiler: &mut Compiler<'a>,
+        name: &Identifier,
+        value: Value<'a>,
+    ) -> CompileResult<()> {
+        let alloca = compiler
+            .builder
+            .build_alloca(value.ty.to_llvm_type(compiler.context), &name.identifier);
 
         compiler.builder.build_store(alloca, value.llvm_value);
+        compiler.own_value(&value, alloca);
+
+        compiler
+            .symbol_table
+            .insert_variable(name.identifier.clone(), value.ty, alloca, name.span)
+    }
+
+    pub fn codegen_global(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let name = match &self.binding {
+            LetBinding::Identifier(name) => name,
+            LetBinding::Tuple(_) => return Err(CompileError::not_constant(self.span)),
+        };
+
+        let value = constant_initializer(compiler, &self.ty, &self.value)?;
+        let ty = value.ty();
+
+        let global =
+            compiler
+                .module
+                .add_global(ty.to_llvm_type(compiler.context), None, &name.identifier);
+        global.set_initializer(&value.codegen(compiler).llvm_value);
 
         compiler.symbol_table.insert_variable(
-            self.name.identifier.clone(),
-            value.ty,
-            alloca,
-            self.name.span,
+            name.identifier.clone(),
+            ty,
+            global.as_pointer_value(),
+            name.span,
         )?;
 
         Ok(())
@@ -116,6 +210,46 @@ impl DisplayNode for LetStatement {
     fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
         display::indent(f, indent)?;
         write!(f, "let ")?;
+        match &self.binding {
+            LetBinding::Identifier(name) => name.display(f, indent)?,
+            LetBinding::Tuple(names) => {
+                let names = names
+                    .iter()
+                    .map(|name| name.identifier.clone())
+                    .collect::<Vec<_>>();
+                write!(f, "({})", names.join(", "))?;
+            }
+        }
+        if let Some(ty) = self.ty.clone() {
+            write!(f, ": {}", ty.kind)?;
+        }
+        write!(f, " = ")?;
+        self.value.display(f, indent)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstStatement {
+    pub name: Identifier,
+    pub ty: Option<AstType>,
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl StatementCodegen for ConstStatement {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let value = constant_initializer(compiler, &self.ty, &self.value)?;
+
+        compiler
+            .symbol_table
+            .insert_constant(self.name.identifier.clone(), value, self.name.span)
+    }
+}
+
+impl DisplayNode for ConstStatement {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "const ")?;
         self.name.display(f, indent)?;
         if let Some(ty) = self.ty.clone() {
             write!(f, ": {}", ty.kind)?;
@@ -125,9 +259,30 @@ impl DisplayNode for LetStatement {
     }
 }
 
+fn constant_initializer(
+    compiler: &Compiler,
+    ty: &Option<AstType>,
+    value: &Expression,
+) -> CompileResult<Constant> {
+    let constant = constant::evaluate(value, &compiler.symbol_table)?;
+    if let Some(ty) = ty {
+        let expected_ty = ty.kind.to_codegen_type(&compiler.symbol_table)?;
+        if expected_ty != constant.ty() {
+            return Err(CompileError::type_mismatch(
+                expected_ty,
+                constant.ty(),
+                value.clone().into(),
+            ));
+        }
+    }
+
+    Ok(constant)
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionDefinition {
     pub name: Identifier,
+    pub type_parameters: Vec<Identifier>,
     pub parameters: Vec<Parameter>,
     pub return_type: AstType,
     pub body: Block,
@@ -140,21 +295,20 @@ pub struct Parameter {
     pub ty: AstType,
 }
 
-impl StatementCodegen for FunctionDefinition {
-    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
-        let mut parameters = Vec::new();
+impl FunctionDefinition {
+    pub fn declare<'a>(&self, compiler: &mut Compiler<'a>) -> CompileResult<FunctionValue<'a>> {
+        let mut parameters_codegen_type = Vec::new();
 
         for parameter in self.parameters.clone() {
-            parameters.push(
-                parameter
-                    .ty
-                    .kind
-                    .to_codegen_type(&compiler.symbol_table)?
-                    .to_llvm_type(compiler.context)
-                    .into(),
-            );
+            parameters_codegen_type
+                .push(parameter.ty.kind.to_codegen_type(&compiler.symbol_table)?);
         }
 
+        let parameters = parameters_codegen_type
+            .iter()
+            .map(|ty| ty.to_llvm_type(compiler.context).into())
+            .collect::<Vec<_>>();
+
         let return_type = self
             .return_type
             .kind
@@ -163,49 +317,72 @@ impl StatementCodegen for FunctionDefinition {
             .to_llvm_type(compiler.context)
             .fn_type(parameters.as_slice(), false);
 
-        let function =
-            compiler
-                .module
-                .add_function(self.name.identifier.as_str(), function_type, None);
-
-        compiler.current_function = Some(CurrentFunction {
-            function,
-            return_type: return_type.clone(),
-        });
-
-        let basic_block = compiler.context.append_basic_block(function, "entry");
-
-        compiler.builder.position_at_end(basic_block);
-
-        let mut parameters_codegen_type = Vec::new();
-
-        for parameter in self.parameters.clone() {
-            parameters_codegen_type
-                .push(parameter.ty.kind.to_codegen_type(&compiler.symbol_table)?);
-        }
-
         compiler.symbol_table.insert_function(
             self.name.identifier.clone(),
             self.name.identifier.clone(),
             function_type,
             FunctionType {
                 name: self.name.identifier.clone(),
-                parameters: parameters_codegen_type.clone(),
-                return_type: Box::new(return_type.clone()),
+                parameters: parameters_codegen_type,
+                variadic: false,
+                return_type: Box::new(return_type),
                 span: self.span,
             },
         )?;
 
+        Ok(compiler
+            .module
+            .add_function(self.name.identifier.as_str(), function_type, None))
+    }
+}
+
+impl StatementCodegen for FunctionDefinition {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        if !self.type_parameters.is_empty() {
+            return compiler.symbol_table.insert_generic_function(self.clone());
+        }
+
+        // Top-level functions are already declared by `Program::codegen`, so that they can be
+        // called before their definition.
+        let function = match compiler.module.get_function(&self.name.identifier) {
+            Some(function) if function.count_basic_blocks() == 0 => function,
+            _ => self.declare(compiler)?,
+        };
+        let FunctionType {
+            parameters: parameters_codegen_type,
+            return_type,
+            ..
+        } = compiler
+            .symbol_table
+            .get_function(&self.name.identifier)
+            .unwrap()
+            .function_type;
+        let return_type = *return_type;
+
+        compiler.current_function = Some(CurrentFunction {
+            function,
+            return_type: return_type.clone(),
+        });
+
+        let basic_block = compiler.context.append_basic_block(function, "entry");
+
+        compiler.builder.position_at_end(basic_block);
+
         let original_symbol_table = compiler.symbol_table.clone();
         compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+        compiler.enter_scope();
 
         for (i, parameter) in function.get_param_iter().enumerate() {
             let parameter_name = self.parameters[i].name.clone();
             let alloca = compiler.builder.build_alloca(
-                compiler.context.i64_type(),
+                parameters_codegen_type[i].to_llvm_type(compiler.context),
                 format!("arg.{}", parameter_name.identifier).as_str(),
             );
             compiler.builder.build_store(alloca, parameter);
+            compiler.own_value(
+                &Value::new(parameter, parameters_codegen_type[i].clone()),
+                alloca,
+            );
 
             compiler.symbol_table.insert_variable(
                 parameter_name.identifier.clone(),
@@ -216,6 +393,7 @@ impl StatementCodegen for FunctionDefinition {
         }
 
         self.body.codegen(compiler)?;
+        compiler.exit_scope();
 
         if return_type != CodegenType::Void && compiler.current_return.is_none() {
             return Err(CompileError::expected("return", self.span));
@@ -223,7 +401,14 @@ impl StatementCodegen for FunctionDefinition {
             compiler.current_return = None;
         }
 
-        if return_type == CodegenType::Void {
+        if return_type == CodegenType::Void
+            && compiler
+                .builder
+                .get_insert_block()
+                .unwrap()
+                .get_terminator()
+                .is_none()
+        {
             compiler.builder.build_return(None);
         }
 
@@ -238,12 +423,15 @@ impl DisplayNode for FunctionDefinition {
         display::indent(f, indent)?;
         write!(f, "define ")?;
         self.name.display(f, indent)?;
+        display_type_parameters(f, &self.type_parameters)?;
 
         if !self.parameters.is_empty() {
             write!(f, "(")?;
             for (i, parameter) in self.parameters.iter().enumerate() {
                 parameter.name.display(f, indent)?;
-                write!(f, " {}", parameter.ty.kind)?;
+                if parameter.name.identifier != "self" {
+                    write!(f, " {}", parameter.ty.kind)?;
+                }
                 if i != self.parameters.len() - 1 {
                     write!(f, ", ")?;
                 }
@@ -257,27 +445,37 @@ impl DisplayNode for FunctionDefinition {
     }
 }
 
+fn display_type_parameters(
+    f: &mut fmt::Formatter<'_>,
+    type_parameters: &[Identifier],
+) -> fmt::Result {
+    if !type_parameters.is_empty() {
+        let type_parameters = type_parameters
+            .iter()
+            .map(|parameter| parameter.identifier.clone())
+            .collect::<Vec<_>>();
+        write!(f, "<{}>", type_parameters.join(", "))?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct ExternalFunctionDeclaration {
     pub alias: Option<Identifier>,
     pub name: Identifier,
     pub parameters: Vec<AstType>,
+    pub variadic: bool,
     pub return_type: AstType,
     pub span: Span,
 }
 
 impl StatementCodegen for ExternalFunctionDeclaration {
     fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
-        let mut parameters = Vec::new();
+        let mut parameters_codegen_type = Vec::new();
 
         for parameter in self.parameters.clone() {
-            parameters.push(
-                parameter
-                    .kind
-                    .to_codegen_type(&compiler.symbol_table)?
-                    .to_llvm_type(compiler.context)
-                    .into(),
-            );
+            parameters_codegen_type.push(parameter.kind.to_codegen_type(&compiler.symbol_table)?);
         }
 
         let return_type = self
@@ -285,33 +483,28 @@ impl StatementCodegen for ExternalFunctionDeclaration {
             .kind
             .to_codegen_type(&compiler.symbol_table)?;
 
-        let function_type = return_type
-            .to_llvm_type(compiler.context)
-            .fn_type(parameters.as_slice(), false);
-
-        compiler
-            .module
-            .add_function(self.name.identifier.as_str(), function_type, None);
-
-        let mut parameters_codegen_type = Vec::new();
-
-        for parameter in self.parameters.clone() {
-            parameters_codegen_type.push(parameter.kind.to_codegen_type(&compiler.symbol_table)?);
-        }
+        let function_type = FunctionType {
+            name: self.name.identifier.clone(),
+            parameters: parameters_codegen_type,
+            variadic: self.variadic,
+            return_type: Box::new(return_type),
+            span: self.span,
+        };
+        let function = abi::declare_external_function(
+            compiler,
+            self.name.identifier.as_str(),
+            &function_type,
+            self.span,
+        )?;
 
         compiler.symbol_table.insert_function(
             self.alias
                 .clone()
                 .unwrap_or_else(|| self.name.clone())
                 .identifier,
-            self.name.identifier.clone(), // TODO: alias
+            function.get_name().to_string_lossy().to_string(), // TODO: alias
+            function.get_type(),
             function_type,
-            FunctionType {
-                name: self.name.identifier.clone(),
-                parameters: parameters_codegen_type.clone(),
-                return_type: Box::new(return_type),
-                span: self.span,
-            },
         )?;
 
         Ok(())
@@ -330,6 +523,12 @@ impl DisplayNode for ExternalFunctionDeclaration {
                 write!(f, ", ")?;
             }
         }
+        if self.variadic {
+            if !self.parameters.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "...")?;
+        }
         write!(f, ") -> {}", self.return_type.kind)
     }
 }
@@ -337,24 +536,37 @@ impl DisplayNode for ExternalFunctionDeclaration {
 #[derive(Debug, Clone)]
 pub struct StructDeclaration {
     pub name: Identifier,
-    pub fields: BTreeMap<String, AstType>,
+    pub type_parameters: Vec<Identifier>,
+    pub fields: IndexMap<String, AstType>,
     pub span: Span,
 }
 
-impl StatementCodegen for StructDeclaration {
-    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
-        let mut fields = BTreeMap::new();
+impl StructDeclaration {
+    pub fn resolve_fields(
+        &self,
+        symbol_table: &SymbolTable,
+    ) -> CompileResult<IndexMap<String, (usize, CodegenType)>> {
+        let mut fields = IndexMap::new();
 
         for (i, (name, ty)) in self.fields.iter().enumerate() {
-            fields.insert(
-                name.clone(),
-                (i, ty.kind.to_codegen_type(&compiler.symbol_table)?.clone()),
-            );
+            fields.insert(name.clone(), (i, ty.kind.to_codegen_type(symbol_table)?));
+        }
+
+        Ok(fields)
+    }
+}
+
+impl StatementCodegen for StructDeclaration {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        // Generic structs are instantiated on demand, once their type arguments are known.
+        if !self.type_parameters.is_empty() {
+            return compiler.symbol_table.insert_generic_struct(self.clone());
         }
 
         let struct_type = StructType {
             name: self.name.identifier.clone(),
-            fields,
+            fields: self.resolve_fields(&compiler.symbol_table)?,
+            type_arguments: Vec::new(),
             span: self.span,
         };
         let struct_llvm_type = compiler
@@ -385,6 +597,7 @@ impl DisplayNode for StructDeclaration {
         display::indent(f, indent)?;
         write!(f, "struct ")?;
         self.name.display(f, indent)?;
+        display_type_parameters(f, &self.type_parameters)?;
         for (name, ty) in self.fields.iter() {
             writeln!(f)?;
             display::indent(f, indent + 1)?;
@@ -395,6 +608,185 @@ impl DisplayNode for StructDeclaration {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct ImplBlock {
+    pub name: Identifier,
+    pub type_parameters: Vec<Identifier>,
+    pub methods: Vec<FunctionDefinition>,
+    pub span: Span,
+}
+
+impl ImplBlock {
+    // Methods are compiled as functions named `Struct.method`, which cannot collide with free
+    // functions, taking a pointer to the struct as their first parameter.
+    fn methods(&self) -> CompileResult<Vec<FunctionDefinition>> {
+        let mut methods = Vec::new();
+
+        for method in self.methods.iter() {
+            let parameter = match method.parameters.first() {
+                Some(parameter) if parameter.name.identifier == "self" => parameter,
+                _ => return Err(CompileError::expected("self", method.span)),
+            };
+
+            // Methods are called with a pointer to the receiver.
+            match &parameter.ty.kind {
+                AstTypeKind::Pointer(ty) if matches!(&ty.kind, AstTypeKind::Struct(name) if name.identifier == self.name.identifier) =>
+                    {}
+                ty => {
+                    return Err(CompileError::type_mismatch(
+                        format!("{}*", self.name.identifier),
+                        ty.to_string(),
+                        parameter.ty.span,
+                    ))
+                }
+            }
+
+            let mut method = method.clone();
+            method.name.identifier = format!("{}.{}", self.name.identifier, method.name.identifier);
+            methods.push(method);
+        }
+
+        Ok(methods)
+    }
+
+    pub fn declare(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        if !self.type_parameters.is_empty()
+            || compiler
+                .symbol_table
+                .get_generic_struct(&self.name.identifier)
+                .is_some()
+        {
+            return Err(CompileError::generic_impl(
+                self.name.identifier.clone(),
+                self.name.span,
+            ));
+        }
+
+        if compiler
+            .symbol_table
+            .get_struct(&self.name.identifier)
+            .is_none()
+        {
+            return Err(CompileError::struct_not_found(
+                self.name.identifier.clone(),
+                self.name.span,
+            ));
+        }
+
+        for method in self.methods()? {
+            if method.type_parameters.is_empty() {
+                method.declare(compiler)?;
+            } else {
+                compiler.symbol_table.insert_generic_function(method)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Top-level impl blocks are already declared by `Program::codegen`, so that their methods can
+    // be called before their definition.
+    pub fn define(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        for method in self.methods()? {
+            if method.type_parameters.is_empty() {
+                method.codegen(compiler)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl StatementCodegen for ImplBlock {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        self.declare(compiler)?;
+        self.define(compiler)
+    }
+}
+
+impl DisplayNode for ImplBlock {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "impl ")?;
+        self.name.display(f, indent)?;
+        display_type_parameters(f, &self.type_parameters)?;
+        for method in self.methods.iter() {
+            writeln!(f)?;
+            method.display(f, indent + 1)?;
+        }
+        writeln!(f)?;
+        display::indent(f, indent)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDeclaration {
+    pub name: Identifier,
+    pub variants: IndexMap<String, IndexMap<String, AstType>>,
+    pub span: Span,
+}
+
+impl StatementCodegen for EnumDeclaration {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let mut variants = IndexMap::new();
+
+        for (name, fields) in self.variants.iter() {
+            let mut codegen_fields = IndexMap::new();
+
+            for (field, ty) in fields.iter() {
+                codegen_fields.insert(
+                    field.clone(),
+                    ty.kind.to_codegen_type(&compiler.symbol_table)?,
+                );
+            }
+
+            variants.insert(name.clone(), codegen_fields);
+        }
+
+        let mut enum_type = EnumType {
+            name: self.name.identifier.clone(),
+            variants,
+            storage_size: 0,
+            span: self.span,
+        };
+        enum_type.storage_size = enum_type
+            .variants
+            .keys()
+            .map(|variant| {
+                let payload = enum_type.variant_llvm_struct_type(compiler.context, variant);
+                compiler.target_data.get_abi_size(&payload).div_ceil(8) as u32
+            })
+            .max()
+            .unwrap_or(0);
+
+        compiler
+            .symbol_table
+            .insert_enum(self.name.identifier.clone(), enum_type)
+    }
+}
+
+impl DisplayNode for EnumDeclaration {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "enum ")?;
+        self.name.display(f, indent)?;
+        for (name, fields) in self.variants.iter() {
+            writeln!(f)?;
+            display::indent(f, indent + 1)?;
+            write!(f, "| {name}")?;
+            if !fields.is_empty() {
+                let fields = fields
+                    .iter()
+                    .map(|(field, ty)| format!("{field} {}", ty.kind))
+                    .collect::<Vec<_>>();
+                write!(f, "({})", fields.join(", "))?;
+            }
+        }
+        writeln!(f)?;
+        display::indent(f, indent)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ReturnStatement {
     pub value: Expression,
@@ -415,6 +807,10 @@ impl StatementCodegen for ReturnStatement {
             ));
         }
 
+        // The caller receives a reference of its own, as the variables of the function go away.
+        compiler.retain_value(&value);
+        compiler.release_references(0, 0);
+
         compiler.builder.build_return(Some(&value.llvm_value));
         compiler.current_return = Some(value);
 
@@ -565,6 +961,8 @@ impl StatementCodegen for While {
         compiler.builder.build_unconditional_branch(condition_block);
 
         compiler.builder.position_at_end(condition_block);
+        // The condition is evaluated on every iteration, so its temporaries are released each time.
+        compiler.enter_statement();
         let condition = self.condition.codegen(compiler)?;
         if condition.ty != CodegenType::Boolean {
             return Err(CompileError::type_mismatch(
@@ -574,20 +972,32 @@ impl StatementCodegen for While {
             ));
         }
 
-        let condition = compiler.builder.build_int_compare(
-            IntPredicate::NE,
-            condition.llvm_value.into_int_value(),
-            compiler.context.i64_type().const_int(0, false),
-            "while.cond",
-        );
+        let condition = condition.llvm_value.into_int_value();
+        compiler.exit_statement();
         compiler
             .builder
             .build_conditional_branch(condition, body_block, end_block);
 
         compiler.builder.position_at_end(body_block);
+
+        compiler.loop_targets.push(LoopTarget {
+            continue_block: condition_block,
+            break_block: end_block,
+            scope_depth: compiler.owner_scopes.len(),
+            temporary_depth: compiler.temporaries.len(),
+        });
         self.body.codegen(compiler)?;
+        compiler.loop_targets.pop();
 
-        compiler.builder.build_unconditional_branch(condition_block);
+        if compiler
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_terminator()
+            .is_none()
+        {
+            compiler.builder.build_unconditional_branch(condition_block);
+        }
 
         compiler.builder.position_at_end(end_block);
 
@@ -630,12 +1040,16 @@ impl StatementCodegen for For {
         let increment_block = compiler.context.append_basic_block(function, "for.inc");
         let end_block = compiler.context.append_basic_block(function, "for.end");
 
+        // The variable belongs to a scope around the whole loop.
+        compiler.enter_scope();
+
         let value = self.initialization.value.codegen(compiler)?;
         let alloca = compiler.builder.build_alloca(
             value.ty.to_llvm_type(compiler.context),
             &self.initialization.name.identifier,
         );
         compiler.builder.build_store(alloca, value.llvm_value);
+        compiler.own_value(&value, alloca);
 
         let original_symbol_table = compiler.symbol_table.clone();
         compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
@@ -650,6 +1064,8 @@ impl StatementCodegen for For {
         compiler.builder.build_unconditional_branch(condition_block);
 
         compiler.builder.position_at_end(condition_block);
+        // The condition is evaluated on every iteration, so its temporaries are released each time.
+        compiler.enter_statement();
         let condition = self.condition.codegen(compiler)?;
         if condition.ty != CodegenType::Boolean {
             return Err(CompileError::type_mismatch(
@@ -659,30 +1075,46 @@ impl StatementCodegen for For {
             ));
         }
 
-        let condition = compiler.builder.build_int_compare(
-            IntPredicate::NE,
-            condition.llvm_value.into_int_value(),
-            compiler.context.i64_type().const_int(0, false),
-            "for.cond",
-        );
+        let condition = condition.llvm_value.into_int_value();
+        compiler.exit_statement();
         compiler
             .builder
             .build_conditional_branch(condition, body_block, end_block);
 
         compiler.builder.position_at_end(body_block);
 
+        compiler.loop_targets.push(LoopTarget {
+            continue_block: increment_block,
+            break_block: end_block,
+            scope_depth: compiler.owner_scopes.len(),
+            temporary_depth: compiler.temporaries.len(),
+        });
+        compiler.enter_scope();
         for statement in self.body.statements.clone() {
             statement.codegen(compiler)?;
         }
+        compiler.exit_scope();
+        compiler.loop_targets.pop();
 
-        compiler.builder.build_unconditional_branch(increment_block);
+        if compiler
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_terminator()
+            .is_none()
+        {
+            compiler.builder.build_unconditional_branch(increment_block);
+        }
 
         compiler.builder.position_at_end(increment_block);
+        compiler.enter_statement();
         self.increment.codegen(compiler)?;
+        compiler.exit_statement();
 
         compiler.builder.build_unconditional_branch(condition_block);
 
         compiler.builder.position_at_end(end_block);
+        compiler.exit_scope();
 
         compiler.symbol_table = original_symbol_table;
 
@@ -705,6 +1137,309 @@ impl DisplayNode for For {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Foreach {
+    pub name: Identifier,
+    pub iterable: Expression,
+    pub body: Block,
+    pub span: Span,
+}
+
+impl StatementCodegen for Foreach {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let function = compiler.current_function.clone().unwrap().function;
+
+        let iterable = self.iterable.codegen(compiler)?;
+        let element_ty = match iterable.ty {
+            CodegenType::Array(ref array_type) => *array_type.ty.clone(),
+            CodegenType::Vec(ref ty) => *ty.clone(),
+            ty => {
+                return Err(CompileError::type_mismatch(
+                    "array or vec",
+                    ty.to_string().as_str(),
+                    self.iterable.clone().into(),
+                ))
+            }
+        };
+
+        let condition_block = compiler
+            .context
+            .append_basic_block(function, "foreach.cond");
+        let body_block = compiler
+            .context
+            .append_basic_block(function, "foreach.body");
+        let increment_block = compiler.context.append_basic_block(function, "foreach.inc");
+        let end_block = compiler.context.append_basic_block(function, "foreach.end");
+
+        let i64_type = compiler.context.i64_type();
+        let element_ll_ty = element_ty.to_llvm_type(compiler.context);
+
+        let index = compiler.builder.build_alloca(i64_type, "foreach.index");
+        compiler
+            .builder
+            .build_store(index, i64_type.const_int(0, false));
+        let element = compiler
+            .builder
+            .build_alloca(element_ll_ty, &self.name.identifier);
+
+        compiler.builder.build_unconditional_branch(condition_block);
+
+        compiler.builder.position_at_end(condition_block);
+        // The body may push to a vec, which can move its elements, so they are looked up anew on
+        // every iteration.
+        let (pointer, len) = match iterable.ty {
+            CodegenType::Vec(_) => compiler.vec_parts(&iterable),
+            _ => compiler.array_parts(&iterable),
+        };
+        let current_index = compiler
+            .builder
+            .build_load(i64_type, index, "foreach.index")
+            .into_int_value();
+        let condition = compiler.builder.build_int_compare(
+            IntPredicate::SLT,
+            current_index,
+            len,
+            "foreach.cond",
+        );
+        compiler
+            .builder
+            .build_conditional_branch(condition, body_block, end_block);
+
+        compiler.builder.position_at_end(body_block);
+
+        let original_symbol_table = compiler.symbol_table.clone();
+        compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+
+        let ptr = unsafe {
+            compiler.builder.build_gep(
+                element_ll_ty,
+                pointer,
+                &[current_index],
+                "ptr.foreach.element",
+            )
+        };
+        let value = compiler
+            .builder
+            .build_load(element_ll_ty, ptr, "foreach.element");
+
+        compiler.loop_targets.push(LoopTarget {
+            continue_block: increment_block,
+            break_block: end_block,
+            scope_depth: compiler.owner_scopes.len(),
+            temporary_depth: compiler.temporaries.len(),
+        });
+        compiler.enter_scope();
+
+        // The variable holds a copy of the element, released at the end of each iteration.
+        compiler.builder.build_store(element, value);
+        compiler.own_value(&Value::new(value, element_ty.clone()), element);
+
+        compiler.symbol_table.insert_variable(
+            self.name.identifier.clone(),
+            element_ty,
+            element,
+            self.name.span,
+        )?;
+        for statement in self.body.statements.clone() {
+            statement.codegen(compiler)?;
+        }
+        compiler.exit_scope();
+        compiler.loop_targets.pop();
+
+        if compiler
+            .builder
+            .get_insert_block()
+            .unwrap()
+            .get_terminator()
+            .is_none()
+        {
+            compiler.builder.build_unconditional_branch(increment_block);
+        }
+
+        compiler.builder.position_at_end(increment_block);
+        let next_index = compiler.builder.build_int_add(
+            current_index,
+            i64_type.const_int(1, false),
+            "foreach.next",
+        );
+        compiler.builder.build_store(index, next_index);
+        compiler.builder.build_unconditional_branch(condition_block);
+
+        compiler.builder.position_at_end(end_block);
+
+        compiler.symbol_table = original_symbol_table;
+
+        Ok(())
+    }
+}
+
+impl DisplayNode for Foreach {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "foreach ")?;
+        self.name.display(f, indent)?;
+        write!(f, " in ")?;
+        self.iterable.display(f, indent)?;
+        self.body.display(f, indent)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteStatement {
+    pub expression: Expression,
+    pub span: Span,
+}
+
+impl StatementCodegen for DeleteStatement {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let value = self.expression.codegen(compiler)?;
+
+        // The references held by the memory are released before it is freed.
+        if let CodegenType::Vec(ref ty) = value.ty {
+            let (pointer, len) = compiler.vec_parts(&value);
+            compiler.release_elements(pointer, len, ty);
+
+            let vec_free = compiler.get_or_declare_function(
+                "vec_free",
+                compiler.context.void_type().fn_type(
+                    &[compiler
+                        .context
+                        .i8_type()
+                        .ptr_type(AddressSpace::from(0))
+                        .into()],
+                    false,
+                ),
+            );
+            compiler
+                .builder
+                .build_call(vec_free, &[value.llvm_value.into()], "");
+
+            return Ok(());
+        }
+
+        // Only memory allocated by `new` may be deleted, which is either a pointer or a slice. The
+        // runtime rejects any other address, such as a slice that starts within the array.
+        let pointer = match value.ty {
+            CodegenType::Pointer(ref ty) => {
+                let pointer = value.llvm_value.into_pointer_value();
+                if ty.holds_references() {
+                    let pointee = compiler.builder.build_load(
+                        ty.to_llvm_type(compiler.context),
+                        pointer,
+                        "delete.value",
+                    );
+                    compiler.release_value(&Value::new(pointee, *ty.clone()));
+                }
+
+                pointer
+            }
+            CodegenType::Array(ref array_type) => {
+                let (pointer, len) = compiler.array_parts(&value);
+                compiler.release_elements(pointer, len, &array_type.ty);
+
+                pointer
+            }
+            _ => {
+                return Err(CompileError::expected(
+                    "pointer, array or vec",
+                    self.expression.clone().into(),
+                ))
+            }
+        };
+
+        let deallocate = compiler.get_or_declare_function(
+            "deallocate",
+            compiler.context.void_type().fn_type(
+                &[compiler
+                    .context
+                    .i8_type()
+                    .ptr_type(AddressSpace::from(0))
+                    .into()],
+                false,
+            ),
+        );
+        compiler
+            .builder
+            .build_call(deallocate, &[pointer.into()], "");
+
+        Ok(())
+    }
+}
+
+impl DisplayNode for DeleteStatement {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "delete ")?;
+        self.expression.display(f, indent)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakStatement {
+    pub span: Span,
+}
+
+impl StatementCodegen for BreakStatement {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let target = match compiler.loop_targets.last() {
+            Some(target) => *target,
+            None => return Err(CompileError::outside_of_loop("break", self.span)),
+        };
+
+        compiler.release_references(target.scope_depth, target.temporary_depth);
+        compiler
+            .builder
+            .build_unconditional_branch(target.break_block);
+        position_after_jump(compiler, "break.after");
+
+        Ok(())
+    }
+}
+
+impl DisplayNode for BreakStatement {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "break")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueStatement {
+    pub span: Span,
+}
+
+impl StatementCodegen for ContinueStatement {
+    fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let target = match compiler.loop_targets.last() {
+            Some(target) => *target,
+            None => return Err(CompileError::outside_of_loop("continue", self.span)),
+        };
+
+        compiler.release_references(target.scope_depth, target.temporary_depth);
+        compiler
+            .builder
+            .build_unconditional_branch(target.continue_block);
+        position_after_jump(compiler, "continue.after");
+
+        Ok(())
+    }
+}
+
+impl DisplayNode for ContinueStatement {
+    fn display(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        display::indent(f, indent)?;
+        write!(f, "continue")
+    }
+}
+
+// Statements following a `break` or `continue` are unreachable, but they still need an
+// unterminated block to be emitted into.
+fn position_after_jump(compiler: &mut Compiler, name: &str) {
+    let function = compiler.current_function.clone().unwrap().function;
+    let block = compiler.context.append_basic_block(function, name);
+    compiler.builder.position_at_end(block);
+}
+
 #[derive(Debug, Clone)]
 pub struct Block {
     pub statements: Vec<Statement>,
@@ -715,11 +1450,13 @@ impl StatementCodegen for Block {
     fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()> {
         let original_symbol_table = compiler.symbol_table.clone();
         compiler.symbol_table = SymbolTable::new_with_parent(compiler.symbol_table.clone());
+        compiler.enter_scope();
 
         for statement in self.statements.clone() {
             statement.codegen(compiler)?;
         }
 
+        compiler.exit_scope();
         compiler.symbol_table = original_symbol_table;
 
         Ok(())
@@ -735,3 +1472,171 @@ impl DisplayNode for Block {
         display::indent(f, indent)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{
+        testing::{
+            block_named, callee, calls, calls_in_block, compile, compile_error, function, in_cycle,
+            instructions, operand_instruction, parameter_type, reference_balances,
+        },
+        CompileErrorKind,
+    };
+    use inkwell::{context::Context, values::InstructionOpcode};
+
+    #[test]
+    fn float_parameter() {
+        assert_eq!(
+            parameter_type("define f(x float) -> float = x * 2.0"),
+            "double"
+        );
+    }
+
+    #[test]
+    fn boolean_parameter() {
+        assert_eq!(parameter_type("define f(x bool) -> int = x ? 1 : 0"), "i1");
+    }
+
+    #[test]
+    fn string_parameter() {
+        assert_eq!(parameter_type("define f(x str) -> str = x"), "ptr");
+    }
+
+    #[test]
+    fn pointer_parameter() {
+        assert_eq!(parameter_type("define f(x int*) -> int = *x"), "ptr");
+    }
+
+    #[test]
+    fn struct_parameter() {
+        assert_eq!(
+            parameter_type(
+                "struct Pair\n    | first int\n    | second float\n\ndefine f(x Pair) -> float = x.second"
+            ),
+            "{ i64, double }"
+        );
+    }
+
+    #[test]
+    fn explicit_self_type() {
+        let context = Context::create();
+        compile(&context, "struct Counter\n    | count int\n\nimpl Counter\n    define get(self Counter*) -> int = self.count");
+
+        assert!(matches!(
+            compile_error("struct Counter\n    | count int\n\nimpl Counter\n    define get(self int) -> int = self"),
+            CompileErrorKind::TypeMismatch(..)
+        ));
+    }
+
+    #[test]
+    fn generic_impl() {
+        assert!(matches!(
+            compile_error(
+                "struct Box<T>\n    | value T\n\nimpl Box\n    define get(self) -> int = 0"
+            ),
+            CompileErrorKind::GenericImpl(_)
+        ));
+        assert!(matches!(
+            compile_error(
+                "struct Box<T>\n    | value T\n\nimpl Box<T>\n    define get(self) -> int = 0"
+            ),
+            CompileErrorKind::GenericImpl(_)
+        ));
+    }
+
+    #[test]
+    fn nested_impl() {
+        let context = Context::create();
+        let module = compile(&context, "define f -> int =\n    struct Counter\n        | count int\n\n    impl Counter\n        define add<T>(self, value T) -> T = value\n\n    let counter = Counter { count: 1 }\n    return counter.add(2)");
+
+        let f = function(&module, "f");
+        assert!(f
+            .get_basic_blocks()
+            .into_iter()
+            .flat_map(instructions)
+            .filter_map(callee)
+            .any(|name| name.starts_with("Counter.add")));
+    }
+
+    #[test]
+    fn foreach_vec() {
+        let context = Context::create();
+        let module = compile(&context, "define sum(numbers vec<int>) -> int =\n    let total = 0\n    foreach n in numbers\n        total = total + n\n    return total");
+
+        // The body may grow the vec, so its data and length are loaded from its header before
+        // each iteration.
+        let condition = block_named(function(&module, "sum"), "foreach.cond");
+        let header_loads = instructions(condition)
+            .into_iter()
+            .filter(|instruction| {
+                instruction.get_opcode() == InstructionOpcode::Load
+                    && operand_instruction(*instruction, 0).is_some_and(|pointer| {
+                        pointer.get_opcode() == InstructionOpcode::GetElementPtr
+                            && pointer.get_parent() == Some(condition)
+                    })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(header_loads.len(), 2);
+
+        let compare = instructions(condition)
+            .into_iter()
+            .find(|instruction| instruction.get_opcode() == InstructionOpcode::ICmp)
+            .unwrap();
+        assert!(header_loads.contains(&operand_instruction(compare, 1).unwrap()));
+    }
+
+    #[test]
+    fn release_deleted_elements() {
+        let context = Context::create();
+        let module = compile(
+            &context,
+            "define clear(names vec<str>) -> int =\n    delete names\n    return 0",
+        );
+
+        // The strings are released by a loop over the elements before the vec itself is freed.
+        let clear = function(&module, "clear");
+        let vec_free = calls(clear, "vec_free");
+        assert_eq!(vec_free.len(), 1);
+        let free_block = vec_free[0].get_parent().unwrap();
+        assert!(!in_cycle(free_block));
+        assert!(clear
+            .get_basic_blocks()
+            .into_iter()
+            .any(|block| in_cycle(block) && !calls_in_block(block, "str_release").is_empty()));
+    }
+
+    #[test]
+    fn loop_condition_temporaries() {
+        let context = Context::create();
+        let module = compile(&context, "extern to_str(int) -> str\n\ndefine count(n int) -> int =\n    let i = 0\n    while len(to_str(i)) < n\n        i = i + 1\n    return i");
+
+        // The string returned by `to_str` is released on every evaluation of the condition.
+        let condition = block_named(function(&module, "count"), "while.cond");
+        assert_eq!(calls_in_block(condition, "to_str.abi").len(), 1);
+        assert_eq!(calls_in_block(condition, "str_release").len(), 1);
+    }
+
+    fn assert_balanced(source: &str) {
+        let context = Context::create();
+        let module = compile(&context, source);
+
+        let f = function(&module, "f");
+        assert!(!calls(f, "str_retain").is_empty());
+        assert!(reference_balances(f).iter().all(|balance| *balance == 0));
+    }
+
+    #[test]
+    fn release_at_scope_exit() {
+        assert_balanced("define f(name str, flag bool) -> int =\n    let copy = name\n    if flag\n        let inner = copy\n    else\n        let other = name\n    return 0");
+    }
+
+    #[test]
+    fn release_on_break_and_continue() {
+        assert_balanced("define f(name str, n int) -> int =\n    let i = 0\n    while i < n\n        let copy = name\n        i = i + 1\n        if i == 2\n            let inner = copy\n            continue\n        if i == 5\n            let inner = copy\n            break\n    return i");
+    }
+
+    #[test]
+    fn release_on_early_return() {
+        assert_balanced("define f(name str, n int) -> int =\n    let copy = name\n    foreach i in [1, 2, 3]\n        let inner = copy\n        if i == n\n            let nested = inner\n            return i\n        else\n            let other = name\n            return 0\n    return 0");
+    }
+}
@@ -1,19 +1,28 @@
 use crate::{
     codegen::{
-        types::{CodegenType, FunctionType, StructType},
+        constant::Constant,
+        statement::{FunctionDefinition, StructDeclaration},
+        types::{
+            generic_name, resolve_type_arguments, CodegenType, EnumType, FunctionType, StructType,
+        },
         CompileError, CompileResult,
     },
-    Span,
+    Compiler, Span, StatementCodegen,
 };
+use indexmap::IndexMap;
 use inkwell::{types, values::PointerValue};
-use std::collections::BTreeMap;
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
 
 #[derive(Debug, Clone, Default)]
 pub struct SymbolEntries<'a> {
     pub variables: BTreeMap<String, VariableEntry<'a>>,
     pub functions: BTreeMap<String, FunctionEntry<'a>>,
     pub structs: BTreeMap<String, StructEntry<'a>>,
+    pub enums: BTreeMap<String, EnumEntry>,
     pub type_aliases: BTreeMap<String, TypeAliasEntry>,
+    pub constants: BTreeMap<String, ConstantEntry>,
+    pub generic_functions: BTreeMap<String, GenericFunctionEntry<'a>>,
+    pub generic_structs: BTreeMap<String, GenericStructEntry>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,11 +44,151 @@ pub struct StructEntry<'a> {
     pub struct_type: StructType,
 }
 
+#[derive(Debug, Clone)]
+pub struct EnumEntry {
+    pub enum_type: EnumType,
+}
+
 #[derive(Debug, Clone)]
 pub struct TypeAliasEntry {
     pub ty: CodegenType,
 }
 
+#[derive(Debug, Clone)]
+pub struct ConstantEntry {
+    pub value: Constant,
+}
+
+// Instances are shared by every clone of the table, so that they outlive the scope in which
+// they were first instantiated.
+#[derive(Debug, Clone)]
+pub struct GenericFunctionEntry<'a> {
+    pub definition: FunctionDefinition,
+    pub instances: Rc<RefCell<BTreeMap<String, FunctionEntry<'a>>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericStructEntry {
+    pub declaration: StructDeclaration,
+    pub instances: Rc<RefCell<BTreeMap<String, StructType>>>,
+}
+
+impl<'a> GenericFunctionEntry<'a> {
+    pub fn instantiate(
+        &self,
+        compiler: &mut Compiler<'a>,
+        argument_types: &[CodegenType],
+        span: Span,
+    ) -> CompileResult<FunctionEntry<'a>> {
+        let definition = &self.definition;
+        if argument_types.len() != definition.parameters.len() {
+            return Err(CompileError::wrong_number_of_arguments(
+                definition.parameters.len(),
+                argument_types.len(),
+                span,
+            ));
+        }
+
+        let mut type_arguments = definition
+            .type_parameters
+            .iter()
+            .map(|parameter| (parameter.identifier.clone(), None))
+            .collect::<IndexMap<_, _>>();
+        for (parameter, ty) in definition.parameters.iter().zip(argument_types) {
+            parameter
+                .ty
+                .kind
+                .infer_type_arguments(ty, &mut type_arguments, span)?;
+        }
+        let type_arguments = resolve_type_arguments(type_arguments, span)?;
+
+        let name = generic_name(&definition.name.identifier, &type_arguments);
+        if let Some(entry) = self.instances.borrow().get(&name) {
+            return Ok(entry.clone());
+        }
+
+        // The instance is compiled as if it were a function defined in the scope declaring the
+        // generic function.
+        let scope = compiler
+            .symbol_table
+            .generic_function_scope(&definition.name.identifier)
+            .unwrap_or_else(|| compiler.symbol_table.root());
+        compiler.detached(|compiler| {
+            compiler.symbol_table = SymbolTable::new_with_parent(scope);
+
+            for (parameter, ty) in definition.type_parameters.iter().zip(type_arguments) {
+                compiler.symbol_table.insert_type_alias(
+                    parameter.identifier.clone(),
+                    ty,
+                    parameter.span,
+                )?;
+            }
+
+            let mut instance = definition.clone();
+            instance.name.identifier = name.clone();
+            instance.type_parameters = Vec::new();
+
+            instance.declare(compiler)?;
+
+            // Cached before the body is compiled, so that recursive calls resolve to the instance.
+            let entry = compiler.symbol_table.get_function(&name).unwrap();
+            self.instances.borrow_mut().insert(name, entry.clone());
+
+            instance.codegen(compiler)?;
+
+            Ok(entry)
+        })
+    }
+}
+
+impl GenericStructEntry {
+    pub fn instantiate(
+        &self,
+        symbol_table: &SymbolTable,
+        type_arguments: Vec<CodegenType>,
+        span: Span,
+    ) -> CompileResult<StructType> {
+        let declaration = &self.declaration;
+        if type_arguments.len() != declaration.type_parameters.len() {
+            return Err(CompileError::wrong_number_of_type_arguments(
+                declaration.type_parameters.len(),
+                type_arguments.len(),
+                span,
+            ));
+        }
+
+        let name = generic_name(&declaration.name.identifier, &type_arguments);
+        if let Some(struct_type) = self.instances.borrow().get(&name) {
+            return Ok(struct_type.clone());
+        }
+
+        let mut symbol_table = SymbolTable::new_with_parent(symbol_table.root());
+        for (parameter, ty) in declaration
+            .type_parameters
+            .iter()
+            .zip(type_arguments.iter())
+        {
+            symbol_table.insert_type_alias(
+                parameter.identifier.clone(),
+                ty.clone(),
+                parameter.span,
+            )?;
+        }
+
+        let struct_type = StructType {
+            name: name.clone(),
+            fields: declaration.resolve_fields(&symbol_table)?,
+            type_arguments,
+            span: declaration.span,
+        };
+        self.instances
+            .borrow_mut()
+            .insert(name, struct_type.clone());
+
+        Ok(struct_type)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SymbolTable<'a> {
     pub entries: SymbolEntries<'a>,
@@ -58,6 +207,28 @@ impl<'a> SymbolTable<'a> {
         }
     }
 
+    pub fn root(&self) -> SymbolTable<'a> {
+        match self.parent {
+            Some(ref parent) => parent.root(),
+            None => self.clone(),
+        }
+    }
+
+    // Variables of every scope but the outermost one, which holds the globals. Variables of
+    // inner scopes shadow those of outer ones.
+    pub fn locals(&self) -> BTreeMap<String, VariableEntry<'a>> {
+        let mut locals = match self.parent {
+            Some(ref parent) => parent.locals(),
+            None => return BTreeMap::new(),
+        };
+        for name in self.entries.constants.keys() {
+            locals.remove(name);
+        }
+        locals.extend(self.entries.variables.clone());
+
+        locals
+    }
+
     pub fn insert_variable(
         &mut self,
         name: String,
@@ -65,7 +236,8 @@ impl<'a> SymbolTable<'a> {
         value: PointerValue<'a>,
         span: Span,
     ) -> CompileResult<()> {
-        if self.entries.variables.contains_key(&name) {
+        if self.entries.variables.contains_key(&name) || self.entries.constants.contains_key(&name)
+        {
             return Err(CompileError::variable_already_declared(name, span));
         }
 
@@ -100,6 +272,54 @@ impl<'a> SymbolTable<'a> {
         Ok(())
     }
 
+    pub fn insert_generic_function(&mut self, definition: FunctionDefinition) -> CompileResult<()> {
+        let name = definition.name.identifier.clone();
+        if self.entries.generic_functions.contains_key(&name) {
+            return Err(CompileError::function_already_declared(
+                name,
+                definition.span,
+            ));
+        }
+
+        self.entries.generic_functions.insert(
+            name,
+            GenericFunctionEntry {
+                definition,
+                instances: Default::default(),
+            },
+        );
+        Ok(())
+    }
+
+    // Structs and enums share the names of types declared in a scope.
+    fn declares_type(&self, name: &str) -> bool {
+        self.entries.structs.contains_key(name)
+            || self.entries.generic_structs.contains_key(name)
+            || self.entries.enums.contains_key(name)
+    }
+
+    pub fn insert_generic_struct(&mut self, declaration: StructDeclaration) -> CompileResult<()> {
+        let name = declaration.name.identifier.clone();
+        if self.entries.generic_structs.contains_key(&name) {
+            return Err(CompileError::struct_already_declared(
+                name,
+                declaration.span,
+            ));
+        }
+        if self.declares_type(&name) {
+            return Err(CompileError::type_already_declared(name, declaration.span));
+        }
+
+        self.entries.generic_structs.insert(
+            name,
+            GenericStructEntry {
+                declaration,
+                instances: Default::default(),
+            },
+        );
+        Ok(())
+    }
+
     pub fn insert_struct(
         &mut self,
         name: String,
@@ -112,6 +332,9 @@ impl<'a> SymbolTable<'a> {
                 struct_type.span,
             ));
         }
+        if self.declares_type(&name) {
+            return Err(CompileError::type_already_declared(name, struct_type.span));
+        }
 
         self.entries
             .structs
@@ -119,6 +342,18 @@ impl<'a> SymbolTable<'a> {
         Ok(())
     }
 
+    pub fn insert_enum(&mut self, name: String, enum_type: EnumType) -> CompileResult<()> {
+        if self.entries.enums.contains_key(&name) {
+            return Err(CompileError::enum_already_declared(name, enum_type.span));
+        }
+        if self.declares_type(&name) {
+            return Err(CompileError::type_already_declared(name, enum_type.span));
+        }
+
+        self.entries.enums.insert(name, EnumEntry { enum_type });
+        Ok(())
+    }
+
     pub fn insert_type_alias(
         &mut self,
         name: String,
@@ -135,9 +370,26 @@ impl<'a> SymbolTable<'a> {
         Ok(())
     }
 
+    pub fn insert_constant(
+        &mut self,
+        name: String,
+        value: Constant,
+        span: Span,
+    ) -> CompileResult<()> {
+        if self.entries.constants.contains_key(&name) || self.entries.variables.contains_key(&name)
+        {
+            return Err(CompileError::variable_already_declared(name, span));
+        }
+
+        self.entries.constants.insert(name, ConstantEntry { value });
+        Ok(())
+    }
+
+    // Variables and constants share a namespace, so either one shadows the other in outer scopes.
     pub fn get_variable(&self, name: &str) -> Option<VariableEntry<'a>> {
         match self.entries.variables.get(name) {
             Some(entry) => Some(entry.clone()),
+            None if self.entries.constants.contains_key(name) => None,
             None => match self.parent {
                 Some(ref parent) => parent.get_variable(name),
                 None => None,
@@ -165,6 +417,44 @@ impl<'a> SymbolTable<'a> {
         }
     }
 
+    pub fn get_generic_function(&self, name: &str) -> Option<GenericFunctionEntry<'a>> {
+        match self.entries.generic_functions.get(name) {
+            Some(entry) => Some(entry.clone()),
+            None => match self.parent {
+                Some(ref parent) => parent.get_generic_function(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn generic_function_scope(&self, name: &str) -> Option<SymbolTable<'a>> {
+        if self.entries.generic_functions.contains_key(name) {
+            return Some(self.clone());
+        }
+
+        self.parent.as_ref()?.generic_function_scope(name)
+    }
+
+    pub fn get_generic_struct(&self, name: &str) -> Option<GenericStructEntry> {
+        match self.entries.generic_structs.get(name) {
+            Some(entry) => Some(entry.clone()),
+            None => match self.parent {
+                Some(ref parent) => parent.get_generic_struct(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn get_enum(&self, name: &str) -> Option<EnumEntry> {
+        match self.entries.enums.get(name) {
+            Some(entry) => Some(entry.clone()),
+            None => match self.parent {
+                Some(ref parent) => parent.get_enum(name),
+                None => None,
+            },
+        }
+    }
+
     pub fn get_type_alias(&self, name: &str) -> Option<TypeAliasEntry> {
         match self.entries.type_aliases.get(name) {
             Some(entry) => Some(entry.clone()),
@@ -174,4 +464,32 @@ impl<'a> SymbolTable<'a> {
             },
         }
     }
+
+    pub fn get_constant(&self, name: &str) -> Option<ConstantEntry> {
+        match self.entries.constants.get(name) {
+            Some(entry) => Some(entry.clone()),
+            None if self.entries.variables.contains_key(name) => None,
+            None => match self.parent {
+                Some(ref parent) => parent.get_constant(name),
+                None => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{testing::compile_error, CompileErrorKind};
+
+    #[test]
+    fn struct_and_enum_share_names() {
+        assert!(matches!(
+            compile_error("struct Foo\n    | x int\n\nenum Foo\n    | Bar\n\ndefine f -> int = 0"),
+            CompileErrorKind::TypeAlreadyDeclared(_)
+        ));
+        assert!(matches!(
+            compile_error("enum Foo\n    | Bar\n\nstruct Foo\n    | x int\n\ndefine f -> int = 0"),
+            CompileErrorKind::TypeAlreadyDeclared(_)
+        ));
+    }
 }
@@ -0,0 +1,219 @@
+// Helpers for the codegen tests, which compile small programs and inspect the structure of the
+// emitted IR.
+use super::{symbol_table::SymbolTable, CompileErrorKind, CompileResult};
+use crate::{lexer::Lexer, parser::Parser, preprocessor::Preprocessor, CompileOptions};
+use inkwell::{
+    basic_block::BasicBlock,
+    context::Context,
+    module::Module,
+    targets::TargetTriple,
+    values::{AnyValue, BasicValue, FunctionValue, InstructionOpcode, InstructionValue},
+};
+
+pub fn codegen<'a>(
+    context: &'a Context,
+    source: &str,
+    options: CompileOptions,
+) -> CompileResult<Module<'a>> {
+    let mut lexer = Lexer::new(source.to_string());
+    lexer.tokenize().unwrap();
+    let tokens = Preprocessor::new(lexer.tokens.into_iter())
+        .preprocess()
+        .unwrap();
+    let program = Parser::new(tokens.into_iter()).parse_program().unwrap();
+
+    program.codegen(
+        context,
+        SymbolTable::default(),
+        &TargetTriple::create("x86_64-unknown-linux-gnu"),
+        "test",
+        options,
+    )
+}
+
+pub fn compile_with<'a>(context: &'a Context, source: &str, options: CompileOptions) -> Module<'a> {
+    let module = codegen(context, source, options).unwrap();
+    module.verify().unwrap();
+    module
+}
+
+pub fn compile<'a>(context: &'a Context, source: &str) -> Module<'a> {
+    compile_with(context, source, CompileOptions::default())
+}
+
+pub fn compile_error(source: &str) -> CompileErrorKind {
+    let context = Context::create();
+
+    codegen(&context, source, CompileOptions::default())
+        .unwrap_err()
+        .kind
+}
+
+pub fn function<'a>(module: &Module<'a>, name: &str) -> FunctionValue<'a> {
+    module
+        .get_function(name)
+        .unwrap_or_else(|| panic!("function `{name}` not found"))
+}
+
+pub fn instructions(block: BasicBlock) -> Vec<InstructionValue> {
+    let mut instructions = Vec::new();
+    let mut instruction = block.get_first_instruction();
+
+    while let Some(current) = instruction {
+        instructions.push(current);
+        instruction = current.get_next_instruction();
+    }
+
+    instructions
+}
+
+pub fn block_named<'a>(function: FunctionValue<'a>, name: &str) -> BasicBlock<'a> {
+    function
+        .get_basic_blocks()
+        .into_iter()
+        .find(|block| block.get_name().to_str() == Ok(name))
+        .unwrap_or_else(|| panic!("block `{name}` not found"))
+}
+
+// The name of the function called by `instruction`, if it is a direct call.
+pub fn callee(instruction: InstructionValue) -> Option<String> {
+    if instruction.get_opcode() != InstructionOpcode::Call {
+        return None;
+    }
+
+    let callee = instruction
+        .get_operand(instruction.get_num_operands() - 1)?
+        .left()?
+        .into_pointer_value();
+    Some(callee.get_name().to_string_lossy().into_owned())
+}
+
+pub fn calls_in_block<'a>(block: BasicBlock<'a>, name: &str) -> Vec<InstructionValue<'a>> {
+    instructions(block)
+        .into_iter()
+        .filter(|instruction| callee(*instruction).as_deref() == Some(name))
+        .collect()
+}
+
+pub fn calls<'a>(function: FunctionValue<'a>, name: &str) -> Vec<InstructionValue<'a>> {
+    function
+        .get_basic_blocks()
+        .into_iter()
+        .flat_map(|block| calls_in_block(block, name))
+        .collect()
+}
+
+pub fn successors(block: BasicBlock) -> Vec<BasicBlock> {
+    let Some(terminator) = block.get_terminator() else {
+        return Vec::new();
+    };
+
+    (0..terminator.get_num_operands())
+        .filter_map(|i| terminator.get_operand(i)?.right())
+        .collect()
+}
+
+// Whether `block` can be reached again after leaving it.
+pub fn in_cycle(block: BasicBlock) -> bool {
+    let mut visited = Vec::new();
+    let mut pending = successors(block);
+
+    while let Some(current) = pending.pop() {
+        if current == block {
+            return true;
+        }
+        if !visited.contains(&current) {
+            visited.push(current);
+            pending.extend(successors(current));
+        }
+    }
+
+    false
+}
+
+fn reference_change(block: BasicBlock) -> i64 {
+    instructions(block)
+        .into_iter()
+        .filter_map(callee)
+        .map(|name| {
+            if name.ends_with("_retain") {
+                1
+            } else if name.ends_with("_release") {
+                -1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+fn walk_references<'a>(
+    block: BasicBlock<'a>,
+    start: Option<BasicBlock<'a>>,
+    path: &mut Vec<BasicBlock<'a>>,
+    balance: i64,
+    balances: &mut Vec<i64>,
+) {
+    let balance = balance + reference_change(block);
+    let terminator = block.get_terminator().unwrap();
+
+    if start.is_none() && terminator.get_opcode() == InstructionOpcode::Return {
+        balances.push(balance);
+    }
+
+    path.push(block);
+    for successor in successors(block) {
+        if Some(successor) == start {
+            balances.push(balance);
+        } else if !path.contains(&successor) {
+            walk_references(successor, start, path, balance, balances);
+        }
+    }
+    path.pop();
+}
+
+// The number of references retained but not released along each path from the entry of `function`
+// to a return and along each cycle through it, counting calls to `*_retain` and `*_release`
+// functions. Paths ending in a panic are not counted.
+pub fn reference_balances(function: FunctionValue) -> Vec<i64> {
+    let mut balances = Vec::new();
+
+    let entry = function.get_first_basic_block().unwrap();
+    walk_references(entry, None, &mut Vec::new(), 0, &mut balances);
+    for block in function.get_basic_blocks() {
+        walk_references(block, Some(block), &mut Vec::new(), 0, &mut balances);
+    }
+
+    balances
+}
+
+// The type allocated by an `alloca` instruction, as printed in the IR.
+pub fn allocated_type(instruction: InstructionValue) -> String {
+    assert_eq!(instruction.get_opcode(), InstructionOpcode::Alloca);
+
+    let printed = instruction.print_to_string().to_string();
+    let ty = printed.split_once("alloca ").unwrap().1;
+    ty.split(", align").next().unwrap().to_string()
+}
+
+// The type of the slot holding the parameter `x` of the function `f` defined by `source`.
+pub fn parameter_type(source: &str) -> String {
+    let context = Context::create();
+    let module = compile(&context, source);
+
+    let entry = function(&module, "f").get_first_basic_block().unwrap();
+    let slot = instructions(entry)
+        .into_iter()
+        .find(|instruction| {
+            instruction.get_name().and_then(|name| name.to_str().ok()) == Some("arg.x")
+        })
+        .unwrap();
+    allocated_type(slot)
+}
+
+pub fn operand_instruction(instruction: InstructionValue, index: u32) -> Option<InstructionValue> {
+    instruction
+        .get_operand(index)?
+        .left()?
+        .as_instruction_value()
+}
@@ -2,13 +2,14 @@ use crate::{
     codegen::{symbol_table::SymbolTable, CompileError, CompileResult, Identifier},
     Span,
 };
+use indexmap::IndexMap;
 use inkwell::{
     context::Context,
-    types::{BasicType, BasicTypeEnum},
+    types::{self, BasicType, BasicTypeEnum},
     values::IntValue,
     AddressSpace,
 };
-use std::{collections::BTreeMap, fmt};
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AstType {
@@ -19,12 +20,18 @@ pub struct AstType {
 #[derive(Debug, PartialEq, Clone)]
 pub enum AstTypeKind {
     Int,
+    Integer(IntegerType),
     Float,
+    F32,
     Boolean,
     String,
     Array(AstArrayTypeKind),
     TypeAlias(Identifier),
     Struct(Identifier),
+    Generic(Identifier, Vec<AstType>),
+    Function(Vec<AstType>, Box<AstType>),
+    Closure(Vec<AstType>, Box<AstType>),
+    Tuple(Vec<AstType>),
     Void,
     Pointer(Box<AstType>),
 }
@@ -40,7 +47,9 @@ impl AstTypeKind {
     pub fn to_codegen_type(&self, symbol_table: &SymbolTable) -> CompileResult<CodegenType> {
         Ok(match self {
             AstTypeKind::Int => CodegenType::Int,
+            AstTypeKind::Integer(ty) => ty.to_codegen_type(),
             AstTypeKind::Float => CodegenType::Float,
+            AstTypeKind::F32 => CodegenType::F32,
             AstTypeKind::Boolean => CodegenType::Boolean,
             AstTypeKind::String => CodegenType::String,
             AstTypeKind::Void => CodegenType::Void,
@@ -58,15 +67,69 @@ impl AstTypeKind {
                     ))
                 }
             },
-            AstTypeKind::Struct(name) => match symbol_table.get_struct(&name.identifier) {
-                Some(struct_type) => CodegenType::Struct(struct_type.struct_type),
-                None => {
-                    return Err(CompileError::struct_not_found(
-                        name.identifier.clone(),
-                        name.span,
-                    ))
-                }
+            // Type parameters are bound as aliases while a generic is instantiated, and shadow
+            // structs and enums of the same name.
+            AstTypeKind::Struct(name) => match symbol_table.get_type_alias(&name.identifier) {
+                Some(entry) => entry.ty,
+                None => match symbol_table.get_struct(&name.identifier) {
+                    Some(struct_type) => CodegenType::Struct(struct_type.struct_type),
+                    None => match symbol_table.get_enum(&name.identifier) {
+                        Some(entry) => CodegenType::Enum(entry.enum_type),
+                        None => {
+                            return Err(CompileError::struct_not_found(
+                                name.identifier.clone(),
+                                name.span,
+                            ))
+                        }
+                    },
+                },
             },
+            AstTypeKind::Function(parameters, return_type) => CodegenType::Function(
+                FunctionType::from_ast(parameters, return_type, symbol_table)?,
+            ),
+            AstTypeKind::Closure(parameters, return_type) => CodegenType::Closure(
+                FunctionType::from_ast(parameters, return_type, symbol_table)?,
+            ),
+            AstTypeKind::Tuple(elements) => {
+                let mut element_types = Vec::new();
+                for element in elements {
+                    element_types.push(element.kind.to_codegen_type(symbol_table)?);
+                }
+
+                CodegenType::Tuple(element_types)
+            }
+            AstTypeKind::Generic(name, arguments) => {
+                let entry = match symbol_table.get_generic_struct(&name.identifier) {
+                    Some(entry) => entry,
+                    // `vec<T>` is built in, unless a generic struct of the same name shadows it.
+                    None if name.identifier == "vec" => {
+                        if arguments.len() != 1 {
+                            return Err(CompileError::wrong_number_of_type_arguments(
+                                1,
+                                arguments.len(),
+                                name.span,
+                            ));
+                        }
+
+                        return Ok(CodegenType::Vec(Box::new(
+                            arguments[0].kind.to_codegen_type(symbol_table)?,
+                        )));
+                    }
+                    None => {
+                        return Err(CompileError::struct_not_found(
+                            name.identifier.clone(),
+                            name.span,
+                        ))
+                    }
+                };
+
+                let mut type_arguments = Vec::new();
+                for argument in arguments {
+                    type_arguments.push(argument.kind.to_codegen_type(symbol_table)?);
+                }
+
+                CodegenType::Struct(entry.instantiate(symbol_table, type_arguments, name.span)?)
+            }
             AstTypeKind::Pointer(ty) => {
                 CodegenType::Pointer(Box::new(ty.kind.to_codegen_type(symbol_table)?))
             }
@@ -74,11 +137,93 @@ impl AstTypeKind {
     }
 }
 
+impl AstTypeKind {
+    // Binds the type parameters occurring in this type by matching it against a concrete type.
+    pub fn infer_type_arguments(
+        &self,
+        ty: &CodegenType,
+        type_arguments: &mut IndexMap<String, Option<CodegenType>>,
+        span: Span,
+    ) -> CompileResult<()> {
+        match (self, ty) {
+            (AstTypeKind::Struct(name), _) if type_arguments.contains_key(&name.identifier) => {
+                match &type_arguments[&name.identifier] {
+                    Some(bound) if bound != ty => {
+                        return Err(CompileError::type_mismatch(bound, ty, span))
+                    }
+                    _ => type_arguments[&name.identifier] = Some(ty.clone()),
+                }
+            }
+            (AstTypeKind::Array(array_type), CodegenType::Array(ty)) => array_type
+                .ty
+                .kind
+                .infer_type_arguments(&ty.ty, type_arguments, span)?,
+            (AstTypeKind::Pointer(inner), CodegenType::Pointer(ty)) => {
+                inner.kind.infer_type_arguments(ty, type_arguments, span)?
+            }
+            (AstTypeKind::Function(parameters, return_type), CodegenType::Function(ty))
+            | (AstTypeKind::Closure(parameters, return_type), CodegenType::Closure(ty)) => {
+                for (parameter, ty) in parameters.iter().zip(ty.parameters.iter()) {
+                    parameter
+                        .kind
+                        .infer_type_arguments(ty, type_arguments, span)?;
+                }
+                return_type
+                    .kind
+                    .infer_type_arguments(&ty.return_type, type_arguments, span)?;
+            }
+            (AstTypeKind::Tuple(elements), CodegenType::Tuple(tys)) => {
+                for (element, ty) in elements.iter().zip(tys.iter()) {
+                    element
+                        .kind
+                        .infer_type_arguments(ty, type_arguments, span)?;
+                }
+            }
+            (AstTypeKind::Generic(_, arguments), CodegenType::Vec(ty)) if arguments.len() == 1 => {
+                arguments[0]
+                    .kind
+                    .infer_type_arguments(ty, type_arguments, span)?
+            }
+            (AstTypeKind::Generic(_, arguments), CodegenType::Struct(struct_type)) => {
+                for (argument, ty) in arguments.iter().zip(struct_type.type_arguments.iter()) {
+                    argument
+                        .kind
+                        .infer_type_arguments(ty, type_arguments, span)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+pub fn resolve_type_arguments(
+    type_arguments: IndexMap<String, Option<CodegenType>>,
+    span: Span,
+) -> CompileResult<Vec<CodegenType>> {
+    type_arguments
+        .into_iter()
+        .map(|(name, ty)| ty.ok_or_else(|| CompileError::cannot_infer_type_parameter(name, span)))
+        .collect()
+}
+
+pub fn generic_name(name: &str, type_arguments: &[CodegenType]) -> String {
+    let type_arguments = type_arguments
+        .iter()
+        .map(|ty| ty.to_string())
+        .collect::<Vec<_>>();
+
+    format!("{name}<{}>", type_arguments.join(", "))
+}
+
 impl fmt::Display for AstTypeKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AstTypeKind::Int => write!(f, "int"),
+            AstTypeKind::Integer(ty) => write!(f, "{ty}"),
             AstTypeKind::Float => write!(f, "float"),
+            AstTypeKind::F32 => write!(f, "f32"),
             AstTypeKind::Boolean => write!(f, "boolean"),
             AstTypeKind::String => write!(f, "str"),
             AstTypeKind::Void => write!(f, "void"),
@@ -94,6 +239,39 @@ impl fmt::Display for AstTypeKind {
             ),
             AstTypeKind::TypeAlias(name) => write!(f, "@{}", name.identifier),
             AstTypeKind::Struct(name) => write!(f, "{}", name.identifier),
+            AstTypeKind::Generic(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.kind.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "{}<{}>", name.identifier, arguments.join(", "))
+            }
+            AstTypeKind::Function(parameters, return_type) => {
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| parameter.kind.to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "define({}) -> {}",
+                    parameters.join(", "),
+                    return_type.kind
+                )
+            }
+            AstTypeKind::Closure(parameters, return_type) => {
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| parameter.kind.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "({}) -> {}", parameters.join(", "), return_type.kind)
+            }
+            AstTypeKind::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.kind.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
             AstTypeKind::Pointer(ty) => write!(f, "{}*", ty.kind),
         }
     }
@@ -102,16 +280,71 @@ impl fmt::Display for AstTypeKind {
 #[derive(Debug, PartialEq, Clone)]
 pub enum CodegenType {
     Int,
+    Integer(IntegerType),
     Float,
+    F32,
     Boolean,
     String,
     Array(ArrayType),
     Struct(StructType),
+    Enum(EnumType),
     Function(FunctionType),
+    Closure(FunctionType),
+    Tuple(Vec<CodegenType>),
+    Vec(Box<CodegenType>),
     Void,
     Pointer(Box<CodegenType>),
 }
 
+// A fixed-width integer other than `int`, which is always a signed 64-bit integer.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IntegerType {
+    pub bits: u32,
+    pub signed: bool,
+}
+
+impl IntegerType {
+    pub const CHAR: IntegerType = IntegerType {
+        bits: 8,
+        signed: false,
+    };
+
+    pub fn new(bits: u32, signed: bool) -> Self {
+        IntegerType { bits, signed }
+    }
+
+    // Parses a type name such as `u8`, which is also how integer literals are suffixed.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let signed = match name.chars().next() {
+            Some('i') => true,
+            Some('u') => false,
+            _ => return None,
+        };
+
+        match &name[1..] {
+            "8" => Some(IntegerType::new(8, signed)),
+            "16" => Some(IntegerType::new(16, signed)),
+            "32" => Some(IntegerType::new(32, signed)),
+            "64" => Some(IntegerType::new(64, signed)),
+            _ => None,
+        }
+    }
+
+    pub fn to_codegen_type(self) -> CodegenType {
+        if self == IntegerType::new(64, true) {
+            CodegenType::Int
+        } else {
+            CodegenType::Integer(self)
+        }
+    }
+}
+
+impl fmt::Display for IntegerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ArrayType {
     pub ty: Box<CodegenType>,
@@ -128,53 +361,168 @@ impl PartialEq for ArrayType {
 #[derive(Debug, PartialEq, Clone)]
 pub struct StructType {
     pub name: String,
-    pub fields: BTreeMap<String, (usize, CodegenType)>,
+    pub fields: IndexMap<String, (usize, CodegenType)>,
+    pub type_arguments: Vec<CodegenType>,
     pub span: Span,
 }
 
+impl StructType {
+    pub fn to_llvm_struct_type<'a>(&self, context: &'a Context) -> types::StructType<'a> {
+        context.struct_type(
+            &self
+                .fields
+                .values()
+                .map(|(_, ty)| ty.to_llvm_type(context))
+                .collect::<Vec<_>>(),
+            false,
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
+pub struct EnumType {
+    pub name: String,
+    pub variants: IndexMap<String, IndexMap<String, CodegenType>>,
+    // Size of the largest payload in 8-byte words, as laid out by the target.
+    pub storage_size: u32,
+    pub span: Span,
+}
+
+impl EnumType {
+    // An enum is lowered to a tag followed by storage large enough for the biggest payload.
+    pub fn to_llvm_struct_type<'a>(&self, context: &'a Context) -> types::StructType<'a> {
+        context.struct_type(
+            &[
+                context.i32_type().into(),
+                context.i64_type().array_type(self.storage_size).into(),
+            ],
+            false,
+        )
+    }
+
+    pub fn variant_llvm_struct_type<'a>(
+        &self,
+        context: &'a Context,
+        variant: &str,
+    ) -> types::StructType<'a> {
+        context.struct_type(
+            &self.variants[variant]
+                .values()
+                .map(|ty| ty.to_llvm_type(context))
+                .collect::<Vec<_>>(),
+            false,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FunctionType {
     pub name: String,
     pub parameters: Vec<CodegenType>,
+    pub variadic: bool,
     pub return_type: Box<CodegenType>,
     pub span: Span,
 }
 
+// Function values of the same signature are interchangeable, whichever function they refer to.
+impl PartialEq for FunctionType {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters
+            && self.variadic == other.variadic
+            && self.return_type == other.return_type
+    }
+}
+
+impl FunctionType {
+    fn from_ast(
+        parameters: &[AstType],
+        return_type: &AstType,
+        symbol_table: &SymbolTable,
+    ) -> CompileResult<Self> {
+        let mut parameter_types = Vec::new();
+        for parameter in parameters {
+            parameter_types.push(parameter.kind.to_codegen_type(symbol_table)?);
+        }
+
+        Ok(FunctionType {
+            name: String::new(),
+            parameters: parameter_types,
+            variadic: false,
+            return_type: Box::new(return_type.kind.to_codegen_type(symbol_table)?),
+            span: return_type.span,
+        })
+    }
+
+    // The code of a closure takes a pointer to its environment before its own parameters.
+    pub fn with_environment(&self) -> Self {
+        let mut parameters = vec![CodegenType::Pointer(Box::new(CodegenType::Void))];
+        parameters.extend(self.parameters.iter().cloned());
+
+        FunctionType {
+            parameters,
+            ..self.clone()
+        }
+    }
+
+    pub fn to_llvm_function_type<'a>(&self, context: &'a Context) -> types::FunctionType<'a> {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|ty| ty.to_llvm_type(context).into())
+            .collect::<Vec<_>>();
+
+        self.return_type
+            .to_llvm_type(context)
+            .fn_type(parameters.as_slice(), self.variadic)
+    }
+}
+
 impl CodegenType {
     pub fn to_llvm_type<'a>(&self, context: &'a Context) -> BasicTypeEnum<'a> {
         match self {
             CodegenType::Int => context.i64_type().into(),
+            CodegenType::Integer(ty) => context.custom_width_int_type(ty.bits).into(),
             CodegenType::Float => context.f64_type().into(),
+            CodegenType::F32 => context.f32_type().into(),
             CodegenType::Boolean => context.bool_type().into(),
             CodegenType::String => context.i8_type().ptr_type(AddressSpace::from(0)).into(),
-            CodegenType::Array(arr) => arr
-                .ty
-                .to_llvm_type(context)
+            CodegenType::Array(_) => context
+                .struct_type(
+                    &[
+                        context.i8_type().ptr_type(AddressSpace::from(0)).into(),
+                        context.i64_type().into(),
+                    ],
+                    false,
+                )
+                .into(),
+            CodegenType::Struct(struct_type) => struct_type.to_llvm_struct_type(context).into(),
+            CodegenType::Enum(enum_type) => enum_type.to_llvm_struct_type(context).into(),
+            CodegenType::Function(function_type) => function_type
+                .to_llvm_function_type(context)
                 .ptr_type(AddressSpace::from(0))
                 .into(),
-            CodegenType::Struct(struct_type) => context
+            // A pointer to the code followed by a pointer to the captured environment.
+            CodegenType::Closure(_) => context
                 .struct_type(
-                    &struct_type
-                        .fields
+                    &[
+                        context.i8_type().ptr_type(AddressSpace::from(0)).into(),
+                        context.i8_type().ptr_type(AddressSpace::from(0)).into(),
+                    ],
+                    false,
+                )
+                .into(),
+            // Tuples are lowered to literal struct types, so equal tuple types are the same type.
+            CodegenType::Tuple(elements) => context
+                .struct_type(
+                    &elements
                         .iter()
-                        .map(|(_, (_, ty))| ty.to_llvm_type(context))
+                        .map(|ty| ty.to_llvm_type(context))
                         .collect::<Vec<_>>(),
                     false,
                 )
-                .ptr_type(AddressSpace::from(0))
                 .into(),
-            CodegenType::Function(function_type) => {
-                let parameters = function_type
-                    .parameters
-                    .iter()
-                    .map(|ty| ty.to_llvm_type(context).into())
-                    .collect::<Vec<_>>();
-                let return_type = function_type.return_type.to_llvm_type(context);
-                return_type
-                    .fn_type(parameters.as_slice(), false)
-                    .ptr_type(AddressSpace::from(0))
-                    .into()
-            }
+            // A pointer to a header owned by the runtime, see `Compiler::vec_parts`.
+            CodegenType::Vec(_) => context.i8_type().ptr_type(AddressSpace::from(0)).into(),
             CodegenType::Pointer(ty) => ty
                 .to_llvm_type(context)
                 .ptr_type(AddressSpace::from(0))
@@ -183,6 +531,42 @@ impl CodegenType {
         }
     }
 
+    // `int` behaves as a signed 64-bit integer wherever integers of any width are accepted.
+    pub fn integer_type(&self) -> Option<IntegerType> {
+        match self {
+            CodegenType::Int => Some(IntegerType::new(64, true)),
+            CodegenType::Integer(ty) => Some(*ty),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.integer_type().is_some()
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, CodegenType::Float | CodegenType::F32)
+    }
+
+    // Strings and closure environments are reference counted, and so are the values holding
+    // them. Arrays, `vec`s and pointers refer to memory whose elements are released separately.
+    pub fn holds_references(&self) -> bool {
+        match self {
+            CodegenType::String | CodegenType::Closure(_) => true,
+            CodegenType::Struct(struct_type) => struct_type
+                .fields
+                .values()
+                .any(|(_, ty)| ty.holds_references()),
+            CodegenType::Enum(enum_type) => enum_type
+                .variants
+                .values()
+                .flat_map(|fields| fields.values())
+                .any(CodegenType::holds_references),
+            CodegenType::Tuple(elements) => elements.iter().any(CodegenType::holds_references),
+            _ => false,
+        }
+    }
+
     pub fn size_of<'a>(&self, context: &'a Context, span: Span) -> CompileResult<IntValue<'a>> {
         Ok(match self.to_llvm_type(context).size_of() {
             Some(size) => size,
@@ -195,7 +579,9 @@ impl fmt::Display for CodegenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CodegenType::Int => write!(f, "int"),
+            CodegenType::Integer(ty) => write!(f, "{ty}"),
             CodegenType::Float => write!(f, "float"),
+            CodegenType::F32 => write!(f, "f32"),
             CodegenType::Boolean => write!(f, "boolean"),
             CodegenType::String => write!(f, "string"),
             CodegenType::Array(arr) => write!(
@@ -209,11 +595,56 @@ impl fmt::Display for CodegenType {
                 }
             ),
             CodegenType::Struct(struct_type) => write!(f, "struct {}", struct_type.name),
+            CodegenType::Enum(enum_type) => write!(f, "enum {}", enum_type.name),
             CodegenType::Function(function_type) => {
-                write!(f, "fn {}", function_type.name)
+                let parameters = function_type
+                    .parameters
+                    .iter()
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "define({}) -> {}",
+                    parameters.join(", "),
+                    function_type.return_type
+                )
+            }
+            CodegenType::Closure(function_type) => {
+                let parameters = function_type
+                    .parameters
+                    .iter()
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "({}) -> {}",
+                    parameters.join(", "),
+                    function_type.return_type
+                )
             }
+            CodegenType::Tuple(elements) => {
+                let elements = elements.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+            CodegenType::Vec(ty) => write!(f, "vec<{ty}>"),
             CodegenType::Void => write!(f, "void"),
             CodegenType::Pointer(ty) => write!(f, "{}*", ty),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::testing::parameter_type;
+
+    #[test]
+    fn enum_storage_size() {
+        // The payload is stored in eightbytes, so three bytes take one of them.
+        assert_eq!(
+            parameter_type(
+                "enum Bytes\n    | Three(a i8, b i8, c i8)\n    | Empty\n\ndefine f(x Bytes) -> int = 0"
+            ),
+            "{ i32, [1 x i64] }"
+        );
+    }
+}
@@ -48,7 +48,9 @@ macro_rules! impl_error_kind {
 impl_error_kind! {
     InvalidEscapeSequence(escape_sequence: String): invalid_escape_sequence<T: ToString>(T) => "invalid escape sequence `{escape_sequence}`",
     UnterminatedStringLiteral: unterminated_string_literal => "unterminated string literal",
-    UnexpectedCharacter(character: String): unexpected_character<T: ToString>(T) => "unexpected character `{character}`"
+    UnexpectedCharacter(character: String): unexpected_character<T: ToString>(T) => "unexpected character `{character}`",
+    InvalidNumberSuffix(suffix: String): invalid_number_suffix<T: ToString>(T) => "invalid number suffix `{suffix}`",
+    IntegerTooLarge(number: String): integer_too_large<T: ToString>(T) => "integer literal `{number}` is too large"
 }
 
 type Result<T> = std::result::Result<T, LexingError>;
@@ -196,7 +198,19 @@ impl Lexer {
         let start_position = self.current_position;
         let mut is_float = false;
 
-        while self.current_char.is_numeric() || self.current_char == '.' {
+        // After a dot, a number is a tuple index, so `pair.0.1` indexes twice.
+        let is_index = matches!(
+            self.tokens.last(),
+            Some(Token {
+                kind: TokenKind::Dot,
+                ..
+            })
+        );
+
+        // A dot followed by another one starts a range, as in `0..10`, rather than a fraction.
+        while self.current_char.is_numeric()
+            || (!is_index && self.current_char == '.' && self.peek_char() != '.')
+        {
             if self.current_char == '.' {
                 if is_float {
                     break;
@@ -208,10 +222,51 @@ impl Lexer {
             self.read_char();
         }
 
-        let token = if is_float {
-            TokenKind::Float(self.input[position..self.position].parse::<f64>().unwrap())
+        let number = self.input[position..self.position].to_string();
+
+        let token = if self.current_char.is_alphabetic() {
+            // Number literals may be suffixed with their type, as in `255u8` or `1.5f32`.
+            let suffix_position = self.position;
+            while self.current_char.is_alphanumeric() {
+                self.read_char();
+            }
+
+            let suffix = &self.input[suffix_position..self.position];
+            match suffix {
+                "f32" | "f64" => {
+                    TokenKind::SuffixedFloat(number.parse::<f64>().unwrap(), suffix.to_string())
+                }
+                "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" if !is_float => {
+                    // Parsed as unsigned, so that the whole range of `u64` can be written.
+                    match number.parse::<u64>() {
+                        Ok(value) => TokenKind::SuffixedInt(value as i64, suffix.to_string()),
+                        Err(_) => {
+                            return Err(LexingError::integer_too_large(
+                                number,
+                                self.span_from(start_position),
+                            ))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(LexingError::invalid_number_suffix(
+                        suffix,
+                        self.span_from(start_position),
+                    ))
+                }
+            }
+        } else if is_float {
+            TokenKind::Float(number.parse::<f64>().unwrap())
         } else {
-            TokenKind::Int(self.input[position..self.position].parse::<i64>().unwrap())
+            match number.parse::<i64>() {
+                Ok(value) => TokenKind::Int(value),
+                Err(_) => {
+                    return Err(LexingError::integer_too_large(
+                        number,
+                        self.span_from(start_position),
+                    ))
+                }
+            }
         };
 
         self.tokens
@@ -376,7 +431,7 @@ impl Lexer {
                 }
             }
             '%' => self.single(TokenKind::Percent),
-            '&' => self.single(TokenKind::Ampersand),
+            '&' => self.double(TokenKind::Ampersand, '&', TokenKind::DoubleAmpersand),
             '@' => self.single(TokenKind::At),
             '#' => self.single(TokenKind::Sharp),
             '.' => {
@@ -391,10 +446,11 @@ impl Lexer {
                         ));
                         Ok(())
                     } else {
-                        Err(LexingError::unexpected_character(
-                            self.current_char.to_string(),
+                        self.tokens.push(Token::new(
+                            TokenKind::DoubleDot,
                             self.span_from(start_position),
-                        ))
+                        ));
+                        Ok(())
                     }
                 } else {
                     self.tokens
@@ -429,9 +485,20 @@ impl Lexer {
                 self.nested_indent -= 1;
                 self.single(TokenKind::RBracket)
             }
+            '<' if self.peek_char() == '<' => {
+                self.read_char();
+                self.read_char();
+                self.tokens.push(Token::new(
+                    TokenKind::LShift,
+                    self.span_from(start_position),
+                ));
+                Ok(())
+            }
             '<' => self.triple(TokenKind::LT, '=', TokenKind::LTE, '-', TokenKind::LArrow),
-            '>' => self.double(TokenKind::GT, '=', TokenKind::GTE),
-            '|' => self.single(TokenKind::Pipe),
+            '>' => self.triple(TokenKind::GT, '=', TokenKind::GTE, '>', TokenKind::RShift),
+            '|' => self.double(TokenKind::Pipe, '|', TokenKind::DoublePipe),
+            '^' => self.single(TokenKind::Caret),
+            '~' => self.single(TokenKind::Tilde),
             '?' => self.single(TokenKind::Question),
             '\0' => {
                 self.tokens
@@ -445,3 +512,50 @@ impl Lexer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{tokens::TokenKind, Lexer};
+
+    fn tokenize(source: &str) -> Vec<TokenKind> {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.tokenize().unwrap();
+
+        lexer.tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn trailing_dot_float() {
+        assert_eq!(tokenize("1.")[0], TokenKind::Float(1.0));
+        assert_eq!(
+            tokenize("1. + 2.5")[..3],
+            [
+                TokenKind::Float(1.0),
+                TokenKind::Plus,
+                TokenKind::Float(2.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn range_after_integer() {
+        assert_eq!(
+            tokenize("0..10")[..3],
+            [TokenKind::Int(0), TokenKind::DoubleDot, TokenKind::Int(10)]
+        );
+    }
+
+    #[test]
+    fn tuple_index_after_dot() {
+        assert_eq!(
+            tokenize("pair.0.1")[..5],
+            [
+                TokenKind::Identifier("pair".to_string()),
+                TokenKind::Dot,
+                TokenKind::Int(0),
+                TokenKind::Dot,
+                TokenKind::Int(1)
+            ]
+        );
+    }
+}
@@ -6,19 +6,21 @@ use std::fmt;
 pub enum TokenKind {
     Indent, Dedent, Newline, EOF,
 
-    Identifier(String), Int(i64), Float(f64), String(String), Boolean(bool), Comment,
+    Identifier(String), Int(i64), SuffixedInt(i64, String), Float(f64), SuffixedFloat(f64, String), String(String), Boolean(bool), Comment,
 
-    Assign, Plus, Minus, Bang, Asterisk, Slash, Percent, Arrow, LArrow, DoubleArrow, Ampersand, At, Sharp, Pipe, Question, Ellipsis,
+    Assign, Plus, Minus, Bang, Asterisk, Slash, Percent, Arrow, LArrow, DoubleArrow, Ampersand, DoubleAmpersand, At, Sharp, Pipe, DoublePipe, Caret, Tilde, Question, Ellipsis,
 
-    Dot, Comma, Colon, DoubleColon, Semicolon,
+    Dot, DoubleDot, Comma, Colon, DoubleColon, Semicolon,
 
     LParen, RParen, LBrace, RBrace, LBracket, RBracket,
 
-    LT, GT, LTE, GTE, EQ, NEQ,
+    LT, GT, LTE, GTE, EQ, NEQ, LShift, RShift,
 
-    Let, Define, If, Else, Return, Type, Struct, While, As, For, Foreach,
+    Let, Const, Define, If, Else, Return, Type, Struct, While, As, For, Foreach, In, Break, Continue, Enum, Match, Impl, New, Delete,
 
-    IntType, FloatType, StringType, BooleanType, VoidType,
+    IntType, FloatType, StringType, BooleanType, VoidType, F32Type, F64Type,
+
+    I8Type, I16Type, I32Type, I64Type, U8Type, U16Type, U32Type, U64Type, CharType,
 
     Extern, Typeof, Sizeof,
 
@@ -29,6 +31,7 @@ impl From<&str> for TokenKind {
     fn from(s: &str) -> Self {
         match s {
             "let" => TokenKind::Let,
+            "const" => TokenKind::Const,
             "define" => TokenKind::Define,
             "if" => TokenKind::If,
             "else" => TokenKind::Else,
@@ -39,6 +42,14 @@ impl From<&str> for TokenKind {
             "as" => TokenKind::As,
             "for" => TokenKind::For,
             "foreach" => TokenKind::Foreach,
+            "in" => TokenKind::In,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "enum" => TokenKind::Enum,
+            "match" => TokenKind::Match,
+            "impl" => TokenKind::Impl,
+            "new" => TokenKind::New,
+            "delete" => TokenKind::Delete,
             "true" => TokenKind::Boolean(true),
             "false" => TokenKind::Boolean(false),
             "int" => TokenKind::IntType,
@@ -46,6 +57,17 @@ impl From<&str> for TokenKind {
             "str" => TokenKind::StringType,
             "bool" => TokenKind::BooleanType,
             "void" => TokenKind::VoidType,
+            "i8" => TokenKind::I8Type,
+            "i16" => TokenKind::I16Type,
+            "i32" => TokenKind::I32Type,
+            "i64" => TokenKind::I64Type,
+            "u8" => TokenKind::U8Type,
+            "u16" => TokenKind::U16Type,
+            "u32" => TokenKind::U32Type,
+            "u64" => TokenKind::U64Type,
+            "char" => TokenKind::CharType,
+            "f32" => TokenKind::F32Type,
+            "f64" => TokenKind::F64Type,
             "extern" => TokenKind::Extern,
             "typeof" => TokenKind::Typeof,
             "sizeof" => TokenKind::Sizeof,
@@ -62,6 +84,8 @@ impl fmt::Display for TokenKind {
             ($( $x:ident )*) => {
                 match &self {
                     $( TokenKind::$x(x) => x.to_string(), )*
+                    TokenKind::SuffixedInt(x, suffix) => format!("{x}{suffix}"),
+                    TokenKind::SuffixedFloat(x, suffix) => format!("{x}{suffix}"),
                     _ => format!("{:?}", self)
                 }
             }
@@ -5,15 +5,21 @@ pub mod preprocessor;
 
 use codegen::{
     symbol_table::SymbolTable,
-    types::{CodegenType, FunctionType, StructType},
-    CompileResult, Statement,
+    types::{ArrayType, CodegenType, EnumType, FunctionType, StructType},
+    CompileError, CompileErrorKind, CompileResult, Statement,
 };
 use inkwell::{
+    basic_block::BasicBlock,
     builder::Builder,
     context::Context,
-    module::Module,
-    targets::TargetTriple,
-    values::{BasicValueEnum, FunctionValue},
+    module::{Linkage, Module},
+    targets::{CodeModel, RelocMode, Target, TargetData, TargetTriple},
+    types::{self, BasicMetadataTypeEnum},
+    values::{
+        AggregateValue, BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue,
+        IntValue, PointerValue,
+    },
+    AddressSpace, IntPredicate, OptimizationLevel,
 };
 use lexer::tokens::TokenKind;
 use std::fmt;
@@ -26,6 +32,634 @@ pub struct Compiler<'a> {
     pub current_function: Option<CurrentFunction<'a>>,
     pub current_return: Option<Value<'a>>,
     pub symbol_table: SymbolTable<'a>,
+    pub loop_targets: Vec<LoopTarget<'a>>,
+    // Places holding references to strings and closures, released when their scope is exited.
+    pub owner_scopes: Vec<Vec<Owner<'a>>>,
+    // Slots holding the values returned by calls, released once their statement has run.
+    pub temporaries: Vec<Vec<(PointerValue<'a>, CodegenType)>>,
+    pub target_data: TargetData,
+    pub options: CompileOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    // Reported along with the line and column of runtime errors.
+    pub source_file: String,
+    pub bounds_checks: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            source_file: String::new(),
+            bounds_checks: true,
+        }
+    }
+}
+
+impl<'a> Compiler<'a> {
+    pub fn get_or_declare_function(
+        &self,
+        name: &str,
+        ty: types::FunctionType<'a>,
+    ) -> FunctionValue<'a> {
+        self.module
+            .get_function(name)
+            .unwrap_or_else(|| self.module.add_function(name, ty, None))
+    }
+
+    // Arrays are passed around as a pointer to their first element followed by their length.
+    pub fn build_array(
+        &self,
+        pointer: PointerValue<'a>,
+        len: IntValue<'a>,
+        array_type: ArrayType,
+    ) -> Value<'a> {
+        let ty = CodegenType::Array(array_type);
+
+        let mut aggregate = ty
+            .to_llvm_type(self.context)
+            .into_struct_type()
+            .get_undef()
+            .as_aggregate_value_enum();
+        aggregate = self
+            .builder
+            .build_insert_value(aggregate, pointer, 0, "array.ptr")
+            .unwrap();
+        aggregate = self
+            .builder
+            .build_insert_value(aggregate, len, 1, "array.len")
+            .unwrap();
+
+        Value::new(aggregate.as_basic_value_enum(), ty)
+    }
+
+    pub fn array_parts(&self, array: &Value<'a>) -> (PointerValue<'a>, IntValue<'a>) {
+        let array = array.llvm_value.into_struct_value();
+
+        let pointer = self
+            .builder
+            .build_extract_value(array, 0, "array.ptr")
+            .unwrap()
+            .into_pointer_value();
+        let len = self
+            .builder
+            .build_extract_value(array, 1, "array.len")
+            .unwrap()
+            .into_int_value();
+
+        (pointer, len)
+    }
+
+    // A `vec` points to a header owned by the runtime, holding its elements, length, capacity and
+    // element size in that order. The elements move when the `vec` grows, so they are loaded
+    // again every time they are accessed.
+    pub fn vec_parts(&self, vec: &Value<'a>) -> (PointerValue<'a>, IntValue<'a>) {
+        let header = vec.llvm_value.into_pointer_value();
+        let header_type = self.context.struct_type(
+            &[
+                self.context
+                    .i8_type()
+                    .ptr_type(AddressSpace::from(0))
+                    .into(),
+                self.context.i64_type().into(),
+                self.context.i64_type().into(),
+                self.context.i64_type().into(),
+            ],
+            false,
+        );
+
+        let pointer = self
+            .builder
+            .build_struct_gep(header_type, header, 0, "ptr.vec.data")
+            .unwrap();
+        let pointer = self
+            .builder
+            .build_load(
+                self.context.i8_type().ptr_type(AddressSpace::from(0)),
+                pointer,
+                "vec.data",
+            )
+            .into_pointer_value();
+        let len = self
+            .builder
+            .build_struct_gep(header_type, header, 1, "ptr.vec.len")
+            .unwrap();
+        let len = self
+            .builder
+            .build_load(self.context.i64_type(), len, "vec.len")
+            .into_int_value();
+
+        (pointer, len)
+    }
+
+    // Panics at runtime unless `0 <= index < len`, reporting where the out-of-bounds index was.
+    pub fn build_bounds_check(&self, index: IntValue<'a>, len: IntValue<'a>, span: Span) {
+        if !self.options.bounds_checks {
+            return;
+        }
+
+        // A negative index is a huge unsigned one, so a single comparison covers both bounds.
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, index, len, "bounds");
+
+        self.build_runtime_check(in_bounds, "panic_index_out_of_bounds", &[index, len], span);
+    }
+
+    // Panics at runtime unless `0 <= start <= end <= len`, as required to slice `start..end`.
+    pub fn build_range_check(
+        &self,
+        start: IntValue<'a>,
+        end: IntValue<'a>,
+        len: IntValue<'a>,
+        span: Span,
+    ) {
+        if !self.options.bounds_checks {
+            return;
+        }
+
+        let ordered =
+            self.builder
+                .build_int_compare(IntPredicate::ULE, start, end, "range.ordered");
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, end, len, "range.bounds");
+        let valid = self.builder.build_and(ordered, in_bounds, "range");
+
+        self.build_runtime_check(valid, "panic_slice_out_of_range", &[start, end, len], span);
+    }
+
+    // Panics at runtime if `len` is negative. Unlike bounds checks, this is never disabled, as the
+    // size of the allocation would wrap around.
+    pub fn build_length_check(&self, len: IntValue<'a>, span: Span) {
+        let valid = self.builder.build_int_compare(
+            IntPredicate::SGE,
+            len,
+            len.get_type().const_zero(),
+            "length",
+        );
+
+        self.build_runtime_check(valid, "panic_negative_length", &[len], span);
+    }
+
+    // Calls the runtime function `panic` with `arguments` and the location of `span` unless
+    // `condition` holds.
+    fn build_runtime_check(
+        &self,
+        condition: IntValue<'a>,
+        panic: &str,
+        arguments: &[IntValue<'a>],
+        span: Span,
+    ) {
+        let function = self.current_function.as_ref().unwrap().function;
+        let i64_type = self.context.i64_type();
+
+        let fail_block = self.context.append_basic_block(function, "bounds.fail");
+        let ok_block = self.context.append_basic_block(function, "bounds.ok");
+        self.builder
+            .build_conditional_branch(condition, ok_block, fail_block);
+
+        self.builder.position_at_end(fail_block);
+
+        let source_file = match self.module.get_global("swua.source_file") {
+            Some(global) => global,
+            None => {
+                let string = self
+                    .context
+                    .const_string(self.options.source_file.as_bytes(), true);
+                let global = self
+                    .module
+                    .add_global(string.get_type(), None, "swua.source_file");
+                global.set_initializer(&string);
+                global.set_constant(true);
+                global.set_linkage(Linkage::Private);
+                global
+            }
+        };
+
+        let mut parameters: Vec<BasicMetadataTypeEnum> = vec![i64_type.into(); arguments.len()];
+        parameters.push(
+            self.context
+                .i8_type()
+                .ptr_type(AddressSpace::from(0))
+                .into(),
+        );
+        parameters.push(i64_type.into());
+        parameters.push(i64_type.into());
+        let panic = self
+            .get_or_declare_function(panic, self.context.void_type().fn_type(&parameters, false));
+
+        let mut values: Vec<BasicMetadataValueEnum> = arguments
+            .iter()
+            .map(|argument| (*argument).into())
+            .collect();
+        values.push(source_file.as_pointer_value().into());
+        values.push(i64_type.const_int(span.start.line as u64, false).into());
+        values.push(i64_type.const_int(span.start.column as u64, false).into());
+        self.builder.build_call(panic, &values, "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(ok_block);
+    }
+
+    // A string points to its bytes, which follow a header holding its reference count and length.
+    // Literals are never freed, which is marked by a negative reference count.
+    pub fn build_string(&self, value: &str) -> PointerValue<'a> {
+        let i64_type = self.context.i64_type();
+        let string = self.context.const_struct(
+            &[
+                i64_type.const_all_ones().into(),
+                i64_type.const_int(value.len() as u64, false).into(),
+                self.context.const_string(value.as_bytes(), true).into(),
+            ],
+            false,
+        );
+
+        // Unlike `build_global_string_ptr`, this does not need an insertion point, so it can be
+        // used to initialize globals.
+        let global = self.module.add_global(string.get_type(), None, ".str");
+        global.set_initializer(&string);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(true);
+
+        let i32_type = self.context.i32_type();
+        unsafe {
+            global.as_pointer_value().const_in_bounds_gep(
+                string.get_type(),
+                &[
+                    i32_type.const_zero(),
+                    i32_type.const_int(2, false),
+                    i32_type.const_zero(),
+                ],
+            )
+        }
+    }
+
+    // Every place holding a string or a closure, such as a variable, a field or an element, keeps
+    // a reference of its own to it.
+    pub fn retain_value(&self, value: &Value<'a>) {
+        self.build_reference_count(value, true);
+    }
+
+    pub fn release_value(&self, value: &Value<'a>) {
+        self.build_reference_count(value, false);
+    }
+
+    fn build_reference_count(&self, value: &Value<'a>, retain: bool) {
+        match &value.ty {
+            CodegenType::String => self.build_runtime_call(
+                if retain { "str_retain" } else { "str_release" },
+                value.llvm_value,
+            ),
+            CodegenType::Closure(_) => {
+                let environment = self
+                    .builder
+                    .build_extract_value(value.llvm_value.into_struct_value(), 1, "closure.env")
+                    .unwrap();
+                self.build_runtime_call(
+                    if retain {
+                        "closure_retain"
+                    } else {
+                        "closure_release"
+                    },
+                    environment,
+                );
+            }
+            CodegenType::Struct(struct_type) => {
+                for (index, ty) in struct_type.fields.values() {
+                    self.build_member_reference_count(value, *index as u32, ty, retain);
+                }
+            }
+            CodegenType::Tuple(elements) => {
+                for (index, ty) in elements.iter().enumerate() {
+                    self.build_member_reference_count(value, index as u32, ty, retain);
+                }
+            }
+            CodegenType::Enum(enum_type) if value.ty.holds_references() => {
+                self.build_payload_reference_count(value, enum_type, retain)
+            }
+            _ => {}
+        }
+    }
+
+    fn build_member_reference_count(
+        &self,
+        value: &Value<'a>,
+        index: u32,
+        ty: &CodegenType,
+        retain: bool,
+    ) {
+        if !ty.holds_references() {
+            return;
+        }
+
+        let member = self
+            .builder
+            .build_extract_value(value.llvm_value.into_struct_value(), index, "member")
+            .unwrap();
+        self.build_reference_count(&Value::new(member, ty.clone()), retain);
+    }
+
+    // Which fields the payload of an enum holds is only known once its tag has been checked.
+    fn build_payload_reference_count(&self, value: &Value<'a>, enum_type: &EnumType, retain: bool) {
+        let function = self.current_function.as_ref().unwrap().function;
+        let enum_llvm_type = enum_type.to_llvm_struct_type(self.context);
+
+        let slot = self.build_entry_slot(enum_llvm_type.into(), "enum.references");
+        self.builder.build_store(slot, value.llvm_value);
+        let tag_ptr = self
+            .builder
+            .build_struct_gep(enum_llvm_type, slot, 0, "enum.tag")
+            .unwrap();
+        let tag = self
+            .builder
+            .build_load(self.context.i32_type(), tag_ptr, "enum.tag")
+            .into_int_value();
+        let payload_ptr = self
+            .builder
+            .build_struct_gep(enum_llvm_type, slot, 1, "enum.payload")
+            .unwrap();
+
+        let end_block = self
+            .context
+            .append_basic_block(function, "enum.references.end");
+        let variants = enum_type
+            .variants
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, fields))| fields.values().any(CodegenType::holds_references))
+            .map(|(tag, (name, fields))| {
+                let block = self
+                    .context
+                    .append_basic_block(function, format!("enum.references.{name}").as_str());
+                (tag, name, fields, block)
+            })
+            .collect::<Vec<_>>();
+
+        let cases = variants
+            .iter()
+            .map(|(tag, _, _, block)| {
+                (
+                    self.context.i32_type().const_int(*tag as u64, false),
+                    *block,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.builder.build_switch(tag, end_block, &cases);
+
+        for (_, name, fields, block) in variants {
+            self.builder.position_at_end(block);
+
+            let variant_llvm_type = enum_type.variant_llvm_struct_type(self.context, name);
+            for (i, ty) in fields.values().enumerate() {
+                if !ty.holds_references() {
+                    continue;
+                }
+
+                let field_ptr = self
+                    .builder
+                    .build_struct_gep(variant_llvm_type, payload_ptr, i as u32, "enum.field")
+                    .unwrap();
+                let field =
+                    self.builder
+                        .build_load(ty.to_llvm_type(self.context), field_ptr, "enum.field");
+                self.build_reference_count(&Value::new(field, ty.clone()), retain);
+            }
+
+            self.builder.build_unconditional_branch(end_block);
+        }
+
+        self.builder.position_at_end(end_block);
+    }
+
+    // Releases the elements of an array or a `vec` before the memory holding them goes away.
+    pub fn release_elements(&self, pointer: PointerValue<'a>, len: IntValue<'a>, ty: &CodegenType) {
+        if !ty.holds_references() {
+            return;
+        }
+
+        let function = self.current_function.as_ref().unwrap().function;
+        let i64_type = self.context.i64_type();
+        let element_type = ty.to_llvm_type(self.context);
+
+        let start_block = self.builder.get_insert_block().unwrap();
+        let condition_block = self.context.append_basic_block(function, "release.cond");
+        let body_block = self.context.append_basic_block(function, "release.body");
+        let end_block = self.context.append_basic_block(function, "release.end");
+
+        self.builder.build_unconditional_branch(condition_block);
+
+        self.builder.position_at_end(condition_block);
+        let index = self.builder.build_phi(i64_type, "release.index");
+        index.add_incoming(&[(&i64_type.const_zero(), start_block)]);
+        let current_index = index.as_basic_value().into_int_value();
+        let condition =
+            self.builder
+                .build_int_compare(IntPredicate::SLT, current_index, len, "release.cond");
+        self.builder
+            .build_conditional_branch(condition, body_block, end_block);
+
+        self.builder.position_at_end(body_block);
+        let element_ptr = unsafe {
+            self.builder.build_gep(
+                element_type,
+                pointer,
+                &[current_index],
+                "ptr.release.element",
+            )
+        };
+        let element = self
+            .builder
+            .build_load(element_type, element_ptr, "release.element");
+        self.release_value(&Value::new(element, ty.clone()));
+        let next_index =
+            self.builder
+                .build_int_add(current_index, i64_type.const_int(1, false), "release.next");
+        index.add_incoming(&[(&next_index, self.builder.get_insert_block().unwrap())]);
+        self.builder.build_unconditional_branch(condition_block);
+
+        self.builder.position_at_end(end_block);
+    }
+
+    fn build_runtime_call(&self, name: &str, pointer: BasicValueEnum<'a>) {
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::from(0));
+        let function = self.get_or_declare_function(
+            name,
+            self.context
+                .void_type()
+                .fn_type(&[i8_ptr_type.into()], false),
+        );
+
+        self.builder.build_call(function, &[pointer.into()], "");
+    }
+
+    // A zeroed slot in the entry block, so that it can be released even when it is only stored to
+    // on some of the paths through the function.
+    fn build_entry_slot(&self, ty: types::BasicTypeEnum<'a>, name: &str) -> PointerValue<'a> {
+        let entry = self
+            .current_function
+            .as_ref()
+            .unwrap()
+            .function
+            .get_first_basic_block()
+            .unwrap();
+
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(instruction) => builder.position_before(&instruction),
+            None => builder.position_at_end(entry),
+        }
+        let slot = builder.build_alloca(ty, name);
+        builder.build_store(slot, ty.const_zero());
+
+        slot
+    }
+
+    // Keeps a value returned by a call, which comes with a reference of its own, alive until the
+    // end of the current statement.
+    pub fn register_temporary(&mut self, value: &Value<'a>) {
+        if !value.ty.holds_references() || self.temporaries.is_empty() {
+            return;
+        }
+
+        let slot = self.build_entry_slot(value.ty.to_llvm_type(self.context), "temporary");
+        self.builder.build_store(slot, value.llvm_value);
+        self.temporaries
+            .last_mut()
+            .unwrap()
+            .push((slot, value.ty.clone()));
+    }
+
+    // Releases the references owned by the scopes and statements entered after the given depths,
+    // before jumping out of them.
+    pub fn release_references(&self, scope_depth: usize, temporary_depth: usize) {
+        for (slot, ty) in self.temporaries[temporary_depth..].iter().flatten() {
+            let llvm_type = ty.to_llvm_type(self.context);
+            let value = self.builder.build_load(llvm_type, *slot, "temporary");
+            self.release_value(&Value::new(value, ty.clone()));
+            self.builder.build_store(*slot, llvm_type.const_zero());
+        }
+
+        for owner in self.owner_scopes[scope_depth..].iter().flatten() {
+            match owner {
+                Owner::Variable(variable, ty) => {
+                    let value =
+                        self.builder
+                            .build_load(ty.to_llvm_type(self.context), *variable, "owned");
+                    self.release_value(&Value::new(value, ty.clone()));
+                }
+                Owner::Elements(slot, ty) => self.release_owned_elements(*slot, ty),
+            }
+        }
+    }
+
+    fn release_owned_elements(&self, slot: PointerValue<'a>, ty: &CodegenType) {
+        let llvm_type = ty.to_llvm_type(self.context);
+        let array = Value::new(
+            self.builder.build_load(llvm_type, slot, "owned.array"),
+            ty.clone(),
+        );
+        let (pointer, len) = self.array_parts(&array);
+
+        if let CodegenType::Array(array_type) = ty {
+            self.release_elements(pointer, len, &array_type.ty);
+        }
+        self.builder.build_store(slot, llvm_type.const_zero());
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.owner_scopes.push(Vec::new());
+    }
+
+    pub fn exit_scope(&mut self) {
+        if !self.owner_scopes.last().unwrap().is_empty() && !self.is_terminated() {
+            self.release_references(self.owner_scopes.len() - 1, self.temporaries.len());
+        }
+        self.owner_scopes.pop();
+    }
+
+    pub fn enter_statement(&mut self) {
+        self.temporaries.push(Vec::new());
+    }
+
+    pub fn exit_statement(&mut self) {
+        if !self.temporaries.last().unwrap().is_empty() && !self.is_terminated() {
+            self.release_references(self.owner_scopes.len(), self.temporaries.len() - 1);
+        }
+        self.temporaries.pop();
+    }
+
+    // Makes a variable own the value it was just initialized with.
+    pub fn own_value(&mut self, value: &Value<'a>, variable: PointerValue<'a>) {
+        if !value.ty.holds_references() {
+            return;
+        }
+
+        self.retain_value(value);
+        if let Some(scope) = self.owner_scopes.last_mut() {
+            scope.push(Owner::Variable(variable, value.ty.clone()));
+        }
+    }
+
+    // Makes the current scope own the elements of an array literal, which live on the stack until
+    // the function returns. The literal may be evaluated again before its scope is exited, as in
+    // the condition of a loop, in which case the elements of the previous array are released.
+    pub fn own_elements(&mut self, array: &Value<'a>) {
+        match &array.ty {
+            CodegenType::Array(array_type) if array_type.ty.holds_references() => {}
+            _ => return,
+        }
+        if self.owner_scopes.is_empty() {
+            return;
+        }
+
+        let slot = self.build_entry_slot(array.ty.to_llvm_type(self.context), "owned.array");
+        self.release_owned_elements(slot, &array.ty);
+        self.builder.build_store(slot, array.llvm_value);
+        self.owner_scopes
+            .last_mut()
+            .unwrap()
+            .push(Owner::Elements(slot, array.ty.clone()));
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .unwrap()
+            .get_terminator()
+            .is_some()
+    }
+
+    // Runs `f` to compile another function in the middle of the current one. The state of the
+    // function currently being compiled is set aside until `f` returns.
+    pub fn detached<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> CompileResult<T>,
+    ) -> CompileResult<T> {
+        let insert_block = self.builder.get_insert_block();
+        let current_function = self.current_function.take();
+        let current_return = self.current_return.take();
+        let loop_targets = std::mem::take(&mut self.loop_targets);
+        let owner_scopes = std::mem::take(&mut self.owner_scopes);
+        let temporaries = std::mem::take(&mut self.temporaries);
+        let symbol_table = self.symbol_table.clone();
+
+        let result = f(self);
+
+        self.symbol_table = symbol_table;
+        self.temporaries = temporaries;
+        self.owner_scopes = owner_scopes;
+        self.loop_targets = loop_targets;
+        self.current_return = current_return;
+        self.current_function = current_function;
+        if let Some(insert_block) = insert_block {
+            self.builder.position_at_end(insert_block);
+        }
+
+        result
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,6 +668,25 @@ pub struct CurrentFunction<'a> {
     pub return_type: CodegenType,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct LoopTarget<'a> {
+    pub continue_block: BasicBlock<'a>,
+    pub break_block: BasicBlock<'a>,
+    // The references owned by the scopes and statements entered after these depths are released
+    // when jumping out of the loop.
+    pub scope_depth: usize,
+    pub temporary_depth: usize,
+}
+
+// A place holding references to strings and closures on behalf of a scope.
+#[derive(Debug, Clone)]
+pub enum Owner<'a> {
+    // A variable, which owns the value it holds.
+    Variable(PointerValue<'a>, CodegenType),
+    // A slot holding an array literal, which owns its elements.
+    Elements(PointerValue<'a>, CodegenType),
+}
+
 pub trait StatementCodegen: Clone {
     fn codegen(&self, compiler: &mut Compiler) -> CompileResult<()>;
 }
@@ -123,9 +776,33 @@ impl Program {
         symbol_table: SymbolTable<'a>,
         triple: &TargetTriple,
         name: &str,
+        options: CompileOptions,
     ) -> CompileResult<Module<'a>> {
+        Target::initialize_all(&Default::default());
+
+        let target_machine = Target::from_triple(triple)
+            .ok()
+            .and_then(|target| {
+                target.create_target_machine(
+                    triple,
+                    "generic",
+                    "",
+                    OptimizationLevel::Default,
+                    RelocMode::Default,
+                    CodeModel::Default,
+                )
+            })
+            .ok_or_else(|| {
+                CompileError::unsupported_target(
+                    triple.as_str().to_string_lossy(),
+                    Position::new(1, 1).into(),
+                )
+            })?;
+        let target_data = target_machine.get_target_data();
+
         let module = context.create_module(name);
         module.set_triple(triple);
+        module.set_data_layout(&target_data.get_data_layout());
         let builder = context.create_builder();
 
         let mut compiler = Compiler {
@@ -135,14 +812,100 @@ impl Program {
             current_function: None,
             current_return: None,
             symbol_table,
+            loop_targets: Vec::new(),
+            owner_scopes: Vec::new(),
+            temporaries: Vec::new(),
+            target_data,
+            options,
         };
 
+        self.declare_types(&mut compiler)?;
+
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Let(statement) => statement.codegen_global(&mut compiler)?,
+                Statement::Const(statement) => statement.codegen(&mut compiler)?,
+                _ => {}
+            }
+        }
+
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Function(function) if !function.type_parameters.is_empty() => {
+                    compiler
+                        .symbol_table
+                        .insert_generic_function(function.clone())?;
+                }
+                Statement::Function(function) => {
+                    function.declare(&mut compiler)?;
+                }
+                Statement::Impl(block) => block.declare(&mut compiler)?,
+                Statement::ExternalFunction(function) => function.codegen(&mut compiler)?,
+                _ => {}
+            }
+        }
+
         for statement in self.statements.clone() {
-            statement.codegen(&mut compiler)?;
+            match statement {
+                // Generic functions are compiled once per instantiation, at their call sites.
+                Statement::Function(function) if !function.type_parameters.is_empty() => {}
+                Statement::Struct(_)
+                | Statement::Enum(_)
+                | Statement::Type(_)
+                | Statement::ExternalFunction(_)
+                | Statement::Let(_)
+                | Statement::Const(_) => {}
+                Statement::Impl(block) => block.define(&mut compiler)?,
+                statement => statement.codegen(&mut compiler)?,
+            }
         }
 
         Ok(compiler.module)
     }
+
+    // Structs, enums and type aliases may refer to each other in any order, so declarations that
+    // depend on a not-yet-declared type are retried until no more progress can be made.
+    fn declare_types(&self, compiler: &mut Compiler) -> CompileResult<()> {
+        let mut pending = self
+            .statements
+            .iter()
+            .filter(|statement| {
+                matches!(
+                    statement,
+                    Statement::Struct(_) | Statement::Enum(_) | Statement::Type(_)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        while !pending.is_empty() {
+            let mut deferred = Vec::new();
+            let mut first_error = None;
+
+            for statement in pending.iter() {
+                match statement.codegen(compiler) {
+                    Ok(()) => {}
+                    Err(error)
+                        if matches!(
+                            error.kind,
+                            CompileErrorKind::StructNotFound(_) | CompileErrorKind::TypeNotFound(_)
+                        ) =>
+                    {
+                        first_error.get_or_insert(error);
+                        deferred.push(*statement);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+
+            if deferred.len() == pending.len() {
+                return Err(first_error.unwrap());
+            }
+
+            pending = deferred;
+        }
+
+        Ok(())
+    }
 }
 
 impl fmt::Display for Program {
@@ -168,6 +931,13 @@ pub enum BinaryOperator {
     GTE,      // A >= B
     LT,       // A < B
     LTE,      // A <= B
+    And,      // A && B
+    Or,       // A || B
+    BitAnd,   // A & B
+    BitOr,    // A | B
+    BitXor,   // A ^ B
+    Shl,      // A << B
+    Shr,      // A >> B
 }
 
 impl From<TokenKind> for BinaryOperator {
@@ -185,6 +955,13 @@ impl From<TokenKind> for BinaryOperator {
             TokenKind::GTE => Self::GTE,
             TokenKind::LT => Self::LT,
             TokenKind::LTE => Self::LTE,
+            TokenKind::DoubleAmpersand => Self::And,
+            TokenKind::DoublePipe => Self::Or,
+            TokenKind::Ampersand => Self::BitAnd,
+            TokenKind::Pipe => Self::BitOr,
+            TokenKind::Caret => Self::BitXor,
+            TokenKind::LShift => Self::Shl,
+            TokenKind::RShift => Self::Shr,
             _ => unreachable!(),
         }
     }
@@ -205,6 +982,13 @@ impl fmt::Display for BinaryOperator {
             Self::GTE => write!(f, ">="),
             Self::LT => write!(f, "<"),
             Self::LTE => write!(f, "<="),
+            Self::And => write!(f, "&&"),
+            Self::Or => write!(f, "||"),
+            Self::BitAnd => write!(f, "&"),
+            Self::BitOr => write!(f, "|"),
+            Self::BitXor => write!(f, "^"),
+            Self::Shl => write!(f, "<<"),
+            Self::Shr => write!(f, ">>"),
         }
     }
 }
@@ -213,6 +997,7 @@ impl fmt::Display for BinaryOperator {
 pub enum UnaryOperator {
     Minus,
     Not,
+    BitNot,
 }
 
 impl From<TokenKind> for UnaryOperator {
@@ -220,6 +1005,7 @@ impl From<TokenKind> for UnaryOperator {
         match token_kind {
             TokenKind::Minus => Self::Minus,
             TokenKind::Bang => Self::Not,
+            TokenKind::Tilde => Self::BitNot,
             _ => unreachable!(),
         }
     }
@@ -230,6 +1016,7 @@ impl fmt::Display for UnaryOperator {
         match self {
             Self::Minus => write!(f, "-"),
             Self::Not => write!(f, "!"),
+            Self::BitNot => write!(f, "~"),
         }
     }
 }
@@ -238,8 +1025,14 @@ impl fmt::Display for UnaryOperator {
 pub enum Priority {
     Lowest,
     Assign_,
+    LogicalOr,
+    LogicalAnd,
+    BitOr,
+    BitXor,
+    BitAnd,
     Equals,
     LessGreater,
+    Shift,
     Sum,
     Product,
     Prefix,
@@ -256,8 +1049,14 @@ impl From<TokenKind> for Priority {
         use TokenKind::*;
         match token_kind {
             Assign | Question => Assign_,
+            DoublePipe => LogicalOr,
+            DoubleAmpersand => LogicalAnd,
+            Pipe => BitOr,
+            Caret => BitXor,
+            Ampersand => BitAnd,
             EQ | NEQ => Equals,
             LT | GT | LTE | GTE => LessGreater,
+            LShift | RShift => Shift,
             Plus | Minus => Sum,
             Slash | Asterisk | Percent => Product,
             Typeof | Sizeof => Prefix,
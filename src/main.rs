@@ -18,6 +18,7 @@ use swua::{
     lexer::Lexer,
     parser::Parser,
     preprocessor::Preprocessor,
+    CompileOptions,
 };
 
 fn compile<'a>(
@@ -25,6 +26,7 @@ fn compile<'a>(
     source_code: String,
     triple: &TargetTriple,
     name: &str,
+    options: CompileOptions,
 ) -> Result<Module<'a>, CompileError> {
     let mut lexer = Lexer::new(source_code);
     lexer.tokenize().map_err(CompileError::from)?;
@@ -37,7 +39,7 @@ fn compile<'a>(
         .map_err(CompileError::from)?;
     // println!("{}", program);
 
-    program.codegen(context, SymbolTable::default(), triple, name)
+    program.codegen(context, SymbolTable::default(), triple, name, options)
 }
 
 fn compile_error(error: CompileError, name: &str, filename: &str, file_content: String) {
@@ -76,6 +78,8 @@ pub struct Cli {
     pub name: Option<String>,
     #[clap(long, help = "Don't print verbose information")]
     pub no_verbose: bool,
+    #[clap(long, help = "Don't check array indices at runtime")]
+    pub no_bounds_checks: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -164,7 +168,17 @@ fn main() {
 
             let source_code = read_file(&input);
             let context = Context::create();
-            let module = match compile(&context, source_code.clone(), &target_triple, &name) {
+            let options = CompileOptions {
+                source_file: input.display().to_string(),
+                bounds_checks: !cli.no_bounds_checks,
+            };
+            let module = match compile(
+                &context,
+                source_code.clone(),
+                &target_triple,
+                &name,
+                options,
+            ) {
                 Ok(module) => module,
                 Err(err) => {
                     compile_error(err, &name, input.to_str().unwrap(), source_code);
@@ -231,7 +245,17 @@ fn main() {
 
             let source_code = read_file(&input);
             let context = Context::create();
-            let module = match compile(&context, source_code.clone(), &target_triple, &name) {
+            let options = CompileOptions {
+                source_file: input.display().to_string(),
+                bounds_checks: !cli.no_bounds_checks,
+            };
+            let module = match compile(
+                &context,
+                source_code.clone(),
+                &target_triple,
+                &name,
+                options,
+            ) {
                 Ok(module) => module,
                 Err(err) => {
                     compile_error(err, &name, input.to_str().unwrap(), source_code);
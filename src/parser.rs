@@ -2,14 +2,17 @@
 
 use crate::{
     codegen::{
-        types::{AstArrayTypeKind, AstType, AstTypeKind},
-        ArrayLiteral, AssignExpression, BinaryExpression, Block, BooleanLiteral, CallExpression,
-        CastExpression, DereferenceExpression, Expression, ExternalFunctionDeclaration,
-        FloatLiteral, For, ForInitialization, FunctionDefinition, Identifier, IfStatement,
-        IndexExpression, IntLiteral, LetStatement, Literal, Parameter, PointerExpression,
-        ReturnStatement, SizeofExpression, Statement, StringLiteral, StructDeclaration,
-        StructLiteral, TernaryExpression, TypeDeclaration, TypeofExpression, UnaryExpression,
-        While,
+        types::{AstArrayTypeKind, AstType, AstTypeKind, IntegerType},
+        ArrayLiteral, AssignExpression, BinaryExpression, Block, BooleanLiteral, BreakStatement,
+        CallExpression, CastExpression, ConstStatement, ContinueStatement, DeleteStatement,
+        DereferenceExpression, EnumDeclaration, EnumLiteral, Expression,
+        ExternalFunctionDeclaration, FloatLiteral, For, ForInitialization, Foreach,
+        FunctionDefinition, Identifier, IfStatement, ImplBlock, IndexExpression, IntLiteral,
+        LambdaExpression, LetBinding, LetStatement, Literal, MatchArm, MatchExpression,
+        NewExpression, NewExpressionKind, Parameter, Pattern, PointerExpression, RangePattern,
+        ReturnStatement, SizeofExpression, SliceExpression, Statement, StringLiteral,
+        StructDeclaration, StructLiteral, TernaryExpression, TupleLiteral, TypeDeclaration,
+        TypeofExpression, UnaryExpression, VariantPattern, While,
     },
     lexer::{
         tokens::{Token, TokenKind},
@@ -17,7 +20,11 @@ use crate::{
     },
     BinaryOperator, DisplayNode, Position, Priority, Program, Span, UnaryOperator,
 };
-use std::{collections::BTreeMap, fmt};
+use indexmap::IndexMap;
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ParsingError {
@@ -72,7 +79,8 @@ impl_error_kind! {
     ExpectedNextToken(expected: String, got: String): expected_next_token<T: ToString>(T, T) => "expected `{expected}` but got `{got}`",
     ExpectedType(expected: String): expected_ty<T: ToString>(T) => "expected type `{expected}`",
     ExpectedExpression(expected: String): expected_expression<T: ToString>(T) => "expected expression `{expected}`",
-    UnexpectedToken(token: String): unexpected_token<T: ToString>(T) => "unexpected token `{token}`"
+    UnexpectedToken(token: String): unexpected_token<T: ToString>(T) => "unexpected token `{token}`",
+    IntegerOutOfRange(ty: String): integer_out_of_range<T: ToString>(T) => "integer literal out of range for `{ty}`"
 }
 
 pub type ParseResult<T> = Result<T, ParsingError>;
@@ -102,8 +110,13 @@ where
     T: Iterator<Item = Token> + Default,
 {
     tokens: T,
+    previous_token: Token,
     current_token: Token,
     peek_token: Token,
+    // The tokens following `peek_token` that were read ahead, or left over from splitting a `>>`.
+    lookahead: VecDeque<Token>,
+    // Names referred to in the bodies of the lambdas being parsed, innermost last.
+    lambda_references: Vec<BTreeSet<String>>,
     span: Span,
 }
 
@@ -139,13 +152,55 @@ where
     }
 
     fn next_token(&mut self) {
+        self.previous_token = self.current_token.clone();
         self.current_token = self.peek_token.clone();
         self.span = self.current_token.span;
 
-        self.peek_token = self.tokens.next().unwrap_or(Token {
+        self.peek_token = match self.lookahead.pop_front() {
+            Some(token) => token,
+            None => self.read_token(),
+        };
+    }
+
+    fn read_token(&mut self) -> Token {
+        self.tokens.next().unwrap_or(Token {
             kind: TokenKind::EOF,
             span: self.span,
-        });
+        })
+    }
+
+    // The kind of the token `n` tokens after the current one, so that `0` is the current token.
+    fn nth_token_kind(&mut self, n: usize) -> TokenKind {
+        match n {
+            0 => self.current_token.kind.clone(),
+            1 => self.peek_token.kind.clone(),
+            n => {
+                while self.lookahead.len() < n - 1 {
+                    let token = self.read_token();
+                    self.lookahead.push_back(token);
+                }
+
+                self.lookahead[n - 2].kind.clone()
+            }
+        }
+    }
+
+    // `>>` closes two nested type argument lists, as in `Box<Box<int>>`.
+    fn split_right_shift(&mut self) {
+        if self.current_token.kind != TokenKind::RShift {
+            return;
+        }
+
+        let mut start = self.span.start;
+        start.column += 1;
+        let second = Token {
+            kind: TokenKind::GT,
+            span: Span::new(start, self.span.end),
+        };
+
+        self.current_token.kind = TokenKind::GT;
+        self.lookahead
+            .push_front(std::mem::replace(&mut self.peek_token, second));
     }
 
     fn expect_token_consume(&mut self, expected: TokenKind) -> ParseResult<()> {
@@ -199,6 +254,15 @@ where
         Ok(())
     }
 
+    // `match` and lambdas with an indented body are already terminated by their closing dedent.
+    fn expect_expression_termination(&mut self, expression: &Expression) -> ParseResult<()> {
+        if self.previous_token.kind == TokenKind::Dedent && ends_with_block(expression) {
+            return Ok(());
+        }
+
+        self.expect_termination()
+    }
+
     fn current_priority(&self) -> Priority {
         Priority::from(self.current_token.kind.clone())
     }
@@ -210,6 +274,7 @@ where
     fn parse_statement(&mut self) -> ParseResult<Statement> {
         Ok(match self.current_token.kind {
             TokenKind::Let => Statement::Let(self.parse_let_statement()?),
+            TokenKind::Const => Statement::Const(self.parse_const_statement()?),
             TokenKind::Define => Statement::Function(self.parse_function_definition()?),
             TokenKind::Extern => {
                 Statement::ExternalFunction(self.parse_external_function_declaration()?)
@@ -218,8 +283,20 @@ where
             TokenKind::If => Statement::If(self.parse_if_statement()?),
             TokenKind::Type => Statement::Type(self.parse_type_statement()?),
             TokenKind::Struct => Statement::Struct(self.parse_struct_declaration()?),
+            TokenKind::Enum => Statement::Enum(self.parse_enum_declaration()?),
+            TokenKind::Impl => Statement::Impl(self.parse_impl_block()?),
+            TokenKind::Match => {
+                let expression = self.parse_match_expression()?;
+                self.next_token();
+
+                Statement::Match(expression)
+            }
             TokenKind::While => Statement::While(self.parse_while_statement()?),
             TokenKind::For => Statement::For(self.parse_for_statement()?),
+            TokenKind::Foreach => Statement::Foreach(self.parse_foreach_statement()?),
+            TokenKind::Break => Statement::Break(self.parse_break_statement()?),
+            TokenKind::Continue => Statement::Continue(self.parse_continue_statement()?),
+            TokenKind::Delete => Statement::Delete(self.parse_delete_statement()?),
             TokenKind::Ellipsis => {
                 self.next_token();
                 Statement::Ellipsis
@@ -232,7 +309,28 @@ where
         let position = self.span.start;
         self.next_token();
 
-        let identifier = identifier! { self };
+        let binding = if self.current_token.kind == TokenKind::LParen {
+            self.next_token();
+
+            let mut names = Vec::new();
+
+            while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                names.push(identifier! { self });
+                self.next_token();
+
+                if self.current_token.kind == TokenKind::RParen {
+                    break;
+                }
+
+                self.expect_token_consume(TokenKind::Comma)?;
+            }
+
+            self.expect_token(TokenKind::RParen)?;
+
+            LetBinding::Tuple(names)
+        } else {
+            LetBinding::Identifier(identifier! { self })
+        };
         self.next_token();
 
         let ty = if self.current_token.kind == TokenKind::Colon {
@@ -256,13 +354,40 @@ where
         }
 
         Ok(LetStatement {
-            name: identifier,
+            binding,
             ty,
             value,
             span: Span::new(position, self.span.end),
         })
     }
 
+    fn parse_const_statement(&mut self) -> ParseResult<ConstStatement> {
+        let LetStatement {
+            binding,
+            ty,
+            value,
+            span,
+        } = self.parse_let_statement()?;
+
+        let name = match binding {
+            LetBinding::Identifier(name) => name,
+            LetBinding::Tuple(_) => {
+                return Err(ParsingError::expected_next_token(
+                    "Identifier".to_string(),
+                    TokenKind::LParen.to_string(),
+                    span,
+                ))
+            }
+        };
+
+        Ok(ConstStatement {
+            name,
+            ty,
+            value,
+            span,
+        })
+    }
+
     fn parse_function_definition(&mut self) -> ParseResult<FunctionDefinition> {
         let position = self.span.start;
         self.next_token();
@@ -270,6 +395,8 @@ where
         let identifier = identifier! { self };
         self.next_token();
 
+        let type_parameters = self.parse_type_parameters()?;
+
         let mut parameters = Vec::new();
 
         if self.current_token.kind == TokenKind::LParen {
@@ -279,8 +406,27 @@ where
                 let identifier = identifier! { self };
                 self.next_token();
 
-                let ty = self.parse_ty()?;
-                self.next_token();
+                // The type of `self` is filled in by the enclosing `impl` block.
+                let ty = if identifier.identifier == "self"
+                    && matches!(
+                        self.current_token.kind,
+                        TokenKind::Comma | TokenKind::RParen
+                    ) {
+                    AstType {
+                        kind: AstTypeKind::Pointer(Box::new(AstType {
+                            kind: AstTypeKind::Struct(Identifier {
+                                identifier: "Self".to_string(),
+                                span: identifier.span,
+                            }),
+                            span: identifier.span,
+                        })),
+                        span: identifier.span,
+                    }
+                } else {
+                    let ty = self.parse_ty()?;
+                    self.next_token();
+                    ty
+                };
 
                 parameters.push(Parameter {
                     name: identifier,
@@ -328,6 +474,7 @@ where
 
         Ok(FunctionDefinition {
             name: identifier,
+            type_parameters,
             parameters,
             return_type,
             body,
@@ -335,6 +482,94 @@ where
         })
     }
 
+    fn parse_lambda_expression(&mut self, position: Position) -> ParseResult<LambdaExpression> {
+        let mut parameters = Vec::new();
+
+        while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+            let identifier = identifier! { self };
+            self.next_token();
+
+            let ty = self.parse_ty()?;
+            self.next_token();
+
+            parameters.push(Parameter {
+                name: identifier,
+                ty,
+            });
+
+            if self.current_token.kind == TokenKind::RParen {
+                break;
+            }
+
+            self.expect_token_consume(TokenKind::Comma)?;
+        }
+
+        self.expect_token_consume(TokenKind::RParen)?;
+        self.expect_token_consume(TokenKind::Arrow)?;
+
+        let return_type = self.parse_ty()?;
+        self.next_token();
+
+        self.expect_token_consume(TokenKind::DoubleArrow)?;
+
+        self.lambda_references.push(BTreeSet::new());
+        let body = self.parse_block();
+        let references = self.lambda_references.pop().unwrap();
+
+        Ok(LambdaExpression {
+            parameters,
+            return_type,
+            body: body?,
+            references,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    // Whether the parenthesis before the current token opens the parameter list of a lambda, which
+    // is followed by the `->` of its return type once closed, as in `(x int) -> int => x * 2`.
+    fn is_parameter_list(&mut self) -> bool {
+        let mut depth = 1;
+        let mut n = 0;
+
+        loop {
+            match self.nth_token_kind(n) {
+                TokenKind::LParen => depth += 1,
+                TokenKind::RParen if depth == 1 => {
+                    return self.nth_token_kind(n + 1) == TokenKind::Arrow;
+                }
+                TokenKind::RParen => depth -= 1,
+                TokenKind::EOF => return false,
+                _ => {}
+            }
+
+            n += 1;
+        }
+    }
+
+    fn parse_type_parameters(&mut self) -> ParseResult<Vec<Identifier>> {
+        let mut type_parameters = Vec::new();
+
+        if self.current_token.kind == TokenKind::LT {
+            self.next_token();
+
+            while self.current_token.kind != TokenKind::GT && !self.is_eof() {
+                type_parameters.push(identifier! { self });
+                self.next_token();
+
+                if self.current_token.kind == TokenKind::GT {
+                    break;
+                }
+
+                self.expect_token_consume(TokenKind::Comma)?;
+            }
+
+            self.expect_token(TokenKind::GT)?;
+            self.next_token();
+        }
+
+        Ok(type_parameters)
+    }
+
     fn parse_block(&mut self) -> ParseResult<Block> {
         let position = self.span.start;
 
@@ -398,20 +633,20 @@ where
         };
 
         let mut parameters = Vec::new();
+        let mut variadic = false;
 
         if self.current_token.kind == TokenKind::LParen {
             self.next_token();
 
             if self.current_token.kind != TokenKind::RParen {
-                let ty = self.parse_ty()?;
-                self.next_token();
-                parameters.push(ty);
-
-                if self.current_token.kind == TokenKind::Comma {
-                    self.next_token();
-                }
-
                 while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                    // `...` ends the parameter list of a variadic function.
+                    if self.current_token.kind == TokenKind::Ellipsis {
+                        variadic = true;
+                        self.next_token();
+                        break;
+                    }
+
                     let ty = self.parse_ty()?;
                     self.next_token();
                     parameters.push(ty);
@@ -440,6 +675,7 @@ where
             alias,
             name: identifier,
             parameters,
+            variadic,
             return_type,
             span: Span::new(position, self.span.end),
         })
@@ -452,7 +688,7 @@ where
         let expression = self.parse_expression(Priority::Lowest)?;
         self.next_token();
 
-        self.expect_termination()?;
+        self.expect_expression_termination(&expression)?;
 
         Ok(ReturnStatement {
             value: expression,
@@ -525,9 +761,11 @@ where
         let identifier = identifier! { self };
         self.next_token();
 
+        let type_parameters = self.parse_type_parameters()?;
+
         self.expect_token_consume(TokenKind::Newline)?;
 
-        let mut fields = BTreeMap::new();
+        let mut fields = IndexMap::new();
 
         if self.current_token.kind == TokenKind::Indent {
             self.next_token();
@@ -562,11 +800,136 @@ where
 
         Ok(StructDeclaration {
             name: identifier,
+            type_parameters,
             fields,
             span: Span::new(position, self.span.end),
         })
     }
 
+    fn parse_impl_block(&mut self) -> ParseResult<ImplBlock> {
+        let position = self.span.start;
+        self.next_token();
+
+        let identifier = identifier! { self };
+        self.next_token();
+
+        let type_parameters = self.parse_type_parameters()?;
+
+        self.expect_token_consume(TokenKind::Newline)?;
+
+        let mut methods = Vec::new();
+
+        if self.current_token.kind == TokenKind::Indent {
+            self.next_token();
+
+            while self.current_token.kind != TokenKind::Dedent && !self.is_eof() {
+                if self.current_token.kind == TokenKind::Newline {
+                    self.next_token();
+                    continue;
+                }
+
+                self.expect_token(TokenKind::Define)?;
+
+                let mut method = self.parse_function_definition()?;
+
+                // An untyped `self` stands for `Self*`. Explicit types are checked against the
+                // struct when the block is compiled.
+                if let Some(parameter) = method.parameters.first_mut() {
+                    if let AstTypeKind::Pointer(ty) = &mut parameter.ty.kind {
+                        if let AstTypeKind::Struct(name) = &mut ty.kind {
+                            if name.identifier == "Self" {
+                                name.identifier = identifier.identifier.clone();
+                            }
+                        }
+                    }
+                }
+
+                methods.push(method);
+            }
+
+            if self.current_token.kind != TokenKind::EOF {
+                self.expect_token_consume(TokenKind::Dedent)?;
+            }
+        }
+
+        Ok(ImplBlock {
+            name: identifier,
+            type_parameters,
+            methods,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    fn parse_enum_declaration(&mut self) -> ParseResult<EnumDeclaration> {
+        let position = self.span.start;
+        self.next_token();
+
+        let identifier = identifier! { self };
+        self.next_token();
+
+        self.expect_token_consume(TokenKind::Newline)?;
+
+        let mut variants = IndexMap::new();
+
+        if self.current_token.kind == TokenKind::Indent {
+            self.next_token();
+
+            while self.current_token.kind != TokenKind::Dedent && !self.is_eof() {
+                self.expect_token_consume(TokenKind::Pipe)?;
+
+                let variant = identifier! { self };
+                self.next_token();
+
+                let mut fields = IndexMap::new();
+
+                if self.current_token.kind == TokenKind::LParen {
+                    self.next_token();
+
+                    while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                        let key = identifier! { self };
+                        self.next_token();
+
+                        let ty = self.parse_ty()?;
+                        self.next_token();
+
+                        fields.insert(key.identifier.clone(), ty);
+
+                        if self.current_token.kind == TokenKind::RParen {
+                            break;
+                        }
+
+                        self.expect_token_consume(TokenKind::Comma)?;
+                    }
+
+                    self.expect_token_consume(TokenKind::RParen)?;
+                }
+
+                variants.insert(variant.identifier.clone(), fields);
+
+                self.expect_token_consume(TokenKind::Newline)?;
+
+                while self.current_token.kind == TokenKind::Newline {
+                    self.next_token();
+                }
+
+                if self.peek_token.kind == TokenKind::Dedent {
+                    self.next_token();
+                    break;
+                }
+            }
+
+            if self.current_token.kind != TokenKind::EOF {
+                self.expect_token_consume(TokenKind::Dedent)?;
+            }
+        }
+
+        Ok(EnumDeclaration {
+            name: identifier,
+            variants,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
     fn parse_while_statement(&mut self) -> ParseResult<While> {
         let position = self.span.start;
         self.next_token();
@@ -626,30 +989,102 @@ where
         })
     }
 
-    fn parse_expression_statement(&mut self) -> ParseResult<Statement> {
+    fn parse_foreach_statement(&mut self) -> ParseResult<Foreach> {
+        let position = self.span.start;
+        self.next_token();
+
+        let identifier = identifier! { self };
+        self.next_token();
+
+        self.expect_token_consume(TokenKind::In)?;
+
+        let iterable = self.parse_expression(Priority::Lowest)?;
+        self.next_token();
+
+        let body = self.parse_block()?;
+        self.next_token();
+
+        Ok(Foreach {
+            name: identifier,
+            iterable,
+            body,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    fn parse_break_statement(&mut self) -> ParseResult<BreakStatement> {
+        let span = self.span;
+        self.next_token();
+
+        self.expect_termination()?;
+
+        Ok(BreakStatement { span })
+    }
+
+    fn parse_continue_statement(&mut self) -> ParseResult<ContinueStatement> {
+        let span = self.span;
+        self.next_token();
+
+        self.expect_termination()?;
+
+        Ok(ContinueStatement { span })
+    }
+
+    fn parse_delete_statement(&mut self) -> ParseResult<DeleteStatement> {
+        let position = self.span.start;
+        self.next_token();
+
         let expression = self.parse_expression(Priority::Lowest)?;
         self.next_token();
 
         self.expect_termination()?;
 
-        Ok(Statement::Expression(expression))
+        Ok(DeleteStatement {
+            expression,
+            span: Span::new(position, self.span.end),
+        })
     }
 
-    fn parse_expression(&mut self, priority: Priority) -> ParseResult<Expression> {
-        let left_expression = match self.current_token.kind.clone() {
-            TokenKind::Identifier(value) => {
+    fn parse_expression_statement(&mut self) -> ParseResult<Statement> {
+        let expression = self.parse_expression(Priority::Lowest)?;
+        self.next_token();
+
+        self.expect_expression_termination(&expression)?;
+
+        Ok(Statement::Expression(expression))
+    }
+
+    fn parse_expression(&mut self, priority: Priority) -> ParseResult<Expression> {
+        let left_expression = match self.current_token.kind.clone() {
+            TokenKind::Identifier(_) if self.peek_token.kind == TokenKind::DoubleColon => Some(Ok(
+                Expression::Literal(Literal::Enum(self.parse_enum_literal()?)),
+            )),
+            TokenKind::Match => return Ok(Expression::Match(self.parse_match_expression()?)),
+            TokenKind::New => Some(Ok(Expression::New(self.parse_new_expression()?))),
+            TokenKind::Identifier(value) => {
+                for references in self.lambda_references.iter_mut() {
+                    references.insert(value.to_string());
+                }
+
                 Some(Ok(Expression::Literal(Literal::Identifier(Identifier {
                     identifier: value.to_string(),
                     span: self.span,
                 }))))
             }
-            TokenKind::Int(value) => Some(Ok(Expression::Literal(Literal::Int(IntLiteral {
-                value,
-                span: self.span,
-            })))),
+            TokenKind::Int(_) | TokenKind::SuffixedInt(..) => Some(Ok(Expression::Literal(
+                Literal::Int(self.parse_int_pattern()?),
+            ))),
             TokenKind::Float(value) => {
                 Some(Ok(Expression::Literal(Literal::Float(FloatLiteral {
                     value,
+                    single_precision: false,
+                    span: self.span,
+                }))))
+            }
+            TokenKind::SuffixedFloat(value, suffix) => {
+                Some(Ok(Expression::Literal(Literal::Float(FloatLiteral {
+                    value,
+                    single_precision: suffix == "f32",
                     span: self.span,
                 }))))
             }
@@ -665,7 +1100,11 @@ where
                     span: self.span,
                 }))))
             }
-            TokenKind::Bang | TokenKind::Minus => {
+            // The sign is part of a suffixed literal, so that `-128i8` is in range.
+            TokenKind::Minus if matches!(self.peek_token.kind, TokenKind::SuffixedInt(..)) => Some(
+                Ok(Expression::Literal(Literal::Int(self.parse_int_pattern()?))),
+            ),
+            TokenKind::Bang | TokenKind::Minus | TokenKind::Tilde => {
                 let operator: UnaryOperator = self.current_token.kind.clone().into();
                 self.next_token();
 
@@ -676,14 +1115,28 @@ where
                 })))
             }
             TokenKind::LParen => {
+                let position = self.span.start;
                 self.next_token();
 
-                let expression = self.parse_expression(Priority::Lowest);
+                if self.is_parameter_list() {
+                    // Like `match`, the body may be an indented block, after which no infix
+                    // operator can follow.
+                    return Ok(Expression::Lambda(self.parse_lambda_expression(position)?));
+                }
+
+                let expression = self.parse_expression(Priority::Lowest)?;
                 self.next_token();
 
-                self.expect_token(TokenKind::RParen)?;
+                // A comma after the first expression makes it a tuple, as in `(1, "one")`.
+                if self.current_token.kind == TokenKind::Comma {
+                    Some(Ok(Expression::Literal(Literal::Tuple(
+                        self.parse_tuple_literal(position, expression)?,
+                    ))))
+                } else {
+                    self.expect_token(TokenKind::RParen)?;
 
-                Some(expression)
+                    Some(Ok(expression))
+                }
             }
             TokenKind::LBracket => Some(Ok(Expression::Literal(Literal::Array(
                 self.parse_array_literal()?,
@@ -749,7 +1202,14 @@ where
                 | TokenKind::LT
                 | TokenKind::GT
                 | TokenKind::LTE
-                | TokenKind::GTE => {
+                | TokenKind::GTE
+                | TokenKind::DoubleAmpersand
+                | TokenKind::DoublePipe
+                | TokenKind::Ampersand
+                | TokenKind::Pipe
+                | TokenKind::Caret
+                | TokenKind::LShift
+                | TokenKind::RShift => {
                     let operator = self.current_token.kind.clone().into();
 
                     let priority = self.current_priority();
@@ -809,16 +1269,47 @@ where
                 TokenKind::LBracket => {
                     self.next_token();
 
-                    let index = self.parse_expression(Priority::Lowest)?;
-                    self.next_token();
+                    let start = if self.current_token.kind == TokenKind::DoubleDot {
+                        None
+                    } else {
+                        let start = self.parse_expression(Priority::Lowest)?;
+                        self.next_token();
 
-                    self.expect_token(TokenKind::RBracket)?;
+                        Some(Box::new(start))
+                    };
 
-                    Ok(Expression::Index(IndexExpression {
-                        left: Box::new(left_expression?),
-                        index: Box::new(index),
-                        span: self.span,
-                    }))
+                    match start {
+                        Some(index) if self.current_token.kind != TokenKind::DoubleDot => {
+                            self.expect_token(TokenKind::RBracket)?;
+
+                            Ok(Expression::Index(IndexExpression {
+                                left: Box::new(left_expression?),
+                                index,
+                                span: self.span,
+                            }))
+                        }
+                        start => {
+                            self.next_token();
+
+                            let end = if self.current_token.kind == TokenKind::RBracket {
+                                None
+                            } else {
+                                let end = self.parse_expression(Priority::Lowest)?;
+                                self.next_token();
+
+                                Some(Box::new(end))
+                            };
+
+                            self.expect_token(TokenKind::RBracket)?;
+
+                            Ok(Expression::Slice(SliceExpression {
+                                left: Box::new(left_expression?),
+                                start,
+                                end,
+                                span: self.span,
+                            }))
+                        }
+                    }
                 }
                 TokenKind::LBrace => {
                     let identifier = match left_expression? {
@@ -832,36 +1323,9 @@ where
                         }
                     };
 
-                    self.expect_token_consume(TokenKind::LBrace)?;
-
-                    let mut fields = BTreeMap::new();
-
-                    while self.current_token.kind != TokenKind::RBrace && !self.is_eof() {
-                        let key = identifier! { self };
-                        self.next_token();
-
-                        self.expect_token_consume(TokenKind::Colon)?;
-
-                        fields.insert(
-                            key.identifier.clone(),
-                            self.parse_expression(Priority::Lowest)?,
-                        );
-                        self.next_token();
-
-                        if self.current_token.kind == TokenKind::RBrace {
-                            break;
-                        }
-
-                        self.expect_token_consume(TokenKind::Comma)?;
-                    }
-
-                    self.expect_token(TokenKind::RBrace)?;
-
-                    Ok(Expression::Literal(Literal::Struct(StructLiteral {
-                        name: identifier,
-                        fields,
-                        span: self.span,
-                    })))
+                    Ok(Expression::Literal(Literal::Struct(
+                        self.parse_struct_literal(identifier, Vec::new())?,
+                    )))
                 }
                 TokenKind::As => {
                     self.next_token();
@@ -897,6 +1361,379 @@ where
         left_expression
     }
 
+    fn parse_enum_literal(&mut self) -> ParseResult<EnumLiteral> {
+        let position = self.span.start;
+
+        let name = identifier! { self };
+        self.next_token();
+        self.next_token();
+
+        let variant = identifier! { self };
+
+        let mut arguments = Vec::new();
+
+        if self.peek_token.kind == TokenKind::LParen {
+            self.next_token();
+            self.next_token();
+
+            while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                arguments.push(self.parse_expression(Priority::Lowest)?);
+                self.next_token();
+
+                if self.current_token.kind == TokenKind::RParen {
+                    break;
+                }
+
+                self.expect_token_consume(TokenKind::Comma)?;
+            }
+
+            self.expect_token(TokenKind::RParen)?;
+        }
+
+        Ok(EnumLiteral {
+            name,
+            variant,
+            arguments,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    fn parse_match_expression(&mut self) -> ParseResult<MatchExpression> {
+        let position = self.span.start;
+        self.next_token();
+
+        let expression = self.parse_expression(Priority::Lowest)?;
+        self.next_token();
+
+        self.expect_token_consume(TokenKind::Newline)?;
+        while self.current_token.kind == TokenKind::Newline {
+            self.next_token();
+        }
+        self.expect_token_consume(TokenKind::Indent)?;
+
+        let mut arms = Vec::new();
+
+        while self.current_token.kind != TokenKind::Dedent && !self.is_eof() {
+            if self.current_token.kind == TokenKind::Newline {
+                self.next_token();
+                continue;
+            }
+
+            let position = self.span.start;
+
+            let pattern = self.parse_pattern()?;
+            self.next_token();
+
+            self.expect_token_consume(TokenKind::DoubleArrow)?;
+
+            let body = if self.current_token.kind == TokenKind::Newline {
+                let block = self.parse_block()?;
+                self.next_token();
+
+                block
+            } else {
+                let position = self.span.start;
+
+                let statement = self.parse_statement()?;
+
+                Block {
+                    statements: vec![statement],
+                    span: Span::new(position, self.span.end),
+                }
+            };
+
+            arms.push(MatchArm {
+                pattern,
+                body,
+                span: Span::new(position, self.span.end),
+            });
+        }
+
+        if self.current_token.kind != TokenKind::Dedent && self.current_token.kind != TokenKind::EOF
+        {
+            return Err(ParsingError::expected_next_token(
+                TokenKind::Dedent.to_string(),
+                self.current_token.kind.to_string(),
+                self.span,
+            ));
+        }
+
+        Ok(MatchExpression {
+            expression: Box::new(expression),
+            arms,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let position = self.span.start;
+
+        match self.current_token.kind.clone() {
+            TokenKind::Int(_) | TokenKind::SuffixedInt(..) | TokenKind::Minus => {
+                let start = self.parse_int_pattern()?;
+
+                if self.peek_token.kind != TokenKind::DoubleDot {
+                    return Ok(Pattern::Literal(Literal::Int(start)));
+                }
+                self.next_token();
+                self.next_token();
+
+                let inclusive = self.current_token.kind == TokenKind::Assign;
+                if inclusive {
+                    self.next_token();
+                }
+
+                let end = self.parse_int_pattern()?;
+
+                return Ok(Pattern::Range(RangePattern {
+                    start,
+                    end,
+                    inclusive,
+                    span: Span::new(position, self.span.end),
+                }));
+            }
+            TokenKind::Boolean(value) => {
+                return Ok(Pattern::Literal(Literal::Boolean(BooleanLiteral {
+                    value,
+                    span: self.span,
+                })))
+            }
+            TokenKind::String(value) => {
+                return Ok(Pattern::Literal(Literal::String(StringLiteral {
+                    value,
+                    span: self.span,
+                })))
+            }
+            _ => {}
+        }
+
+        let identifier = identifier! { self };
+
+        if identifier.identifier == "_" {
+            return Ok(Pattern::Wildcard(self.span));
+        }
+
+        let (name, variant) = if self.peek_token.kind == TokenKind::DoubleColon {
+            self.next_token();
+            self.next_token();
+
+            (Some(identifier), identifier! { self })
+        } else {
+            (None, identifier)
+        };
+
+        let mut bindings = Vec::new();
+
+        if self.peek_token.kind == TokenKind::LParen {
+            self.next_token();
+            self.next_token();
+
+            while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                bindings.push(identifier! { self });
+                self.next_token();
+
+                if self.current_token.kind == TokenKind::RParen {
+                    break;
+                }
+
+                self.expect_token_consume(TokenKind::Comma)?;
+            }
+
+            self.expect_token(TokenKind::RParen)?;
+        }
+
+        Ok(Pattern::Variant(VariantPattern {
+            name,
+            variant,
+            bindings,
+            span: Span::new(position, self.span.end),
+        }))
+    }
+
+    fn parse_int_pattern(&mut self) -> ParseResult<IntLiteral> {
+        let position = self.span.start;
+
+        let negative = self.current_token.kind == TokenKind::Minus;
+        if negative {
+            self.next_token();
+        }
+
+        match &self.current_token.kind {
+            TokenKind::Int(value) => Ok(IntLiteral {
+                value: if negative { -value } else { *value },
+                ty: None,
+                span: Span::new(position, self.span.end),
+            }),
+            TokenKind::SuffixedInt(value, suffix) => {
+                let ty = IntegerType::from_name(suffix).unwrap();
+
+                // The lexer keeps the magnitude as the bit pattern of a `u64`.
+                let magnitude = *value as u64;
+                let limit = match (ty.signed, negative) {
+                    (true, true) => 1 << (ty.bits - 1),
+                    (true, false) => (1 << (ty.bits - 1)) - 1,
+                    (false, true) => 0,
+                    (false, false) => u64::MAX >> (64 - ty.bits),
+                };
+                if magnitude > limit {
+                    return Err(ParsingError::integer_out_of_range(
+                        ty,
+                        Span::new(position, self.span.end),
+                    ));
+                }
+
+                Ok(IntLiteral {
+                    value: if negative {
+                        (magnitude as i64).wrapping_neg()
+                    } else {
+                        magnitude as i64
+                    },
+                    ty: Some(ty),
+                    span: Span::new(position, self.span.end),
+                })
+            }
+            _ => Err(ParsingError::expected_next_token(
+                "Int".to_string(),
+                self.current_token.kind.to_string(),
+                self.span,
+            )),
+        }
+    }
+
+    fn parse_new_expression(&mut self) -> ParseResult<NewExpression> {
+        let position = self.span.start;
+        self.next_token();
+
+        let is_type = matches!(
+            self.current_token.kind,
+            TokenKind::IntType
+                | TokenKind::I8Type
+                | TokenKind::I16Type
+                | TokenKind::I32Type
+                | TokenKind::I64Type
+                | TokenKind::U8Type
+                | TokenKind::U16Type
+                | TokenKind::U32Type
+                | TokenKind::U64Type
+                | TokenKind::CharType
+                | TokenKind::FloatType
+                | TokenKind::F32Type
+                | TokenKind::F64Type
+                | TokenKind::StringType
+                | TokenKind::BooleanType
+                | TokenKind::Identifier(_)
+        );
+
+        // Unlike in a type, the length of `new T[n]` need not be a constant.
+        let kind = if is_type && self.peek_token.kind == TokenKind::LBracket {
+            let ty = AstType {
+                kind: self.parse_base_ty()?,
+                span: self.span,
+            };
+            self.next_token();
+            self.next_token();
+
+            let len = self.parse_expression(Priority::Lowest)?;
+            self.next_token();
+
+            self.expect_token(TokenKind::RBracket)?;
+
+            NewExpressionKind::Array(ty, Box::new(len))
+        } else if matches!(self.current_token.kind, TokenKind::Identifier(_))
+            && self.opens_type_arguments()
+        {
+            let kind = self.parse_base_ty()?;
+
+            // `new Pair<int, int> { ... }` allocates a struct literal with explicit type arguments.
+            match kind {
+                AstTypeKind::Generic(name, type_arguments)
+                    if self.peek_token.kind == TokenKind::LBrace =>
+                {
+                    self.next_token();
+
+                    NewExpressionKind::Value(Box::new(Expression::Literal(Literal::Struct(
+                        self.parse_struct_literal(name, type_arguments)?,
+                    ))))
+                }
+                kind => NewExpressionKind::Type(AstType {
+                    kind,
+                    span: self.span,
+                }),
+            }
+        } else {
+            NewExpressionKind::Value(Box::new(self.parse_expression(Priority::Prefix)?))
+        };
+
+        Ok(NewExpression {
+            kind,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
+    fn parse_struct_literal(
+        &mut self,
+        name: Identifier,
+        type_arguments: Vec<AstType>,
+    ) -> ParseResult<StructLiteral> {
+        self.expect_token_consume(TokenKind::LBrace)?;
+
+        let mut fields = IndexMap::new();
+
+        while self.current_token.kind != TokenKind::RBrace && !self.is_eof() {
+            let key = identifier! { self };
+            self.next_token();
+
+            self.expect_token_consume(TokenKind::Colon)?;
+
+            fields.insert(
+                key.identifier.clone(),
+                self.parse_expression(Priority::Lowest)?,
+            );
+            self.next_token();
+
+            if self.current_token.kind == TokenKind::RBrace {
+                break;
+            }
+
+            self.expect_token_consume(TokenKind::Comma)?;
+        }
+
+        self.expect_token(TokenKind::RBrace)?;
+
+        Ok(StructLiteral {
+            name,
+            type_arguments,
+            fields,
+            span: self.span,
+        })
+    }
+
+    fn parse_tuple_literal(
+        &mut self,
+        position: Position,
+        first: Expression,
+    ) -> ParseResult<TupleLiteral> {
+        let mut elements = vec![first];
+
+        while self.current_token.kind == TokenKind::Comma {
+            self.next_token();
+
+            if self.current_token.kind == TokenKind::RParen {
+                break;
+            }
+
+            elements.push(self.parse_expression(Priority::Lowest)?);
+            self.next_token();
+        }
+
+        self.expect_token(TokenKind::RParen)?;
+
+        Ok(TupleLiteral {
+            elements,
+            span: Span::new(position, self.span.end),
+        })
+    }
+
     fn parse_array_literal(&mut self) -> ParseResult<ArrayLiteral> {
         let position = self.span.start;
         self.next_token();
@@ -929,19 +1766,164 @@ where
         })
     }
 
-    fn parse_ty(&mut self) -> ParseResult<AstType> {
-        let mut ty = match &self.current_token.kind {
+    // Whether the `<` after the current token opens a list of type arguments, which is closed by a
+    // `>` before any token that cannot be part of a type. Otherwise it is a comparison, as in
+    // `x as T < y`.
+    fn opens_type_arguments(&mut self) -> bool {
+        if self.peek_token.kind != TokenKind::LT {
+            return false;
+        }
+
+        let mut depth = 1;
+        let mut n = 2;
+
+        loop {
+            match self.nth_token_kind(n) {
+                TokenKind::LT => depth += 1,
+                TokenKind::GT if depth == 1 => return true,
+                TokenKind::GT => depth -= 1,
+                TokenKind::RShift if depth <= 2 => return true,
+                TokenKind::RShift => depth -= 2,
+                TokenKind::Identifier(_)
+                | TokenKind::IntType
+                | TokenKind::I8Type
+                | TokenKind::I16Type
+                | TokenKind::I32Type
+                | TokenKind::I64Type
+                | TokenKind::U8Type
+                | TokenKind::U16Type
+                | TokenKind::U32Type
+                | TokenKind::U64Type
+                | TokenKind::CharType
+                | TokenKind::FloatType
+                | TokenKind::F32Type
+                | TokenKind::F64Type
+                | TokenKind::StringType
+                | TokenKind::BooleanType
+                | TokenKind::VoidType
+                | TokenKind::Define
+                | TokenKind::At
+                | TokenKind::Int(_)
+                | TokenKind::Comma
+                | TokenKind::Asterisk
+                | TokenKind::LBracket
+                | TokenKind::RBracket
+                | TokenKind::LParen
+                | TokenKind::RParen
+                | TokenKind::Arrow => {}
+                _ => return false,
+            }
+
+            n += 1;
+        }
+    }
+
+    // A type without the array and pointer suffixes that may follow it.
+    fn parse_base_ty(&mut self) -> ParseResult<AstTypeKind> {
+        let has_type_arguments = self.opens_type_arguments();
+
+        match &self.current_token.kind {
             TokenKind::IntType => Ok(AstTypeKind::Int),
+            TokenKind::I8Type => Ok(AstTypeKind::Integer(IntegerType::new(8, true))),
+            TokenKind::I16Type => Ok(AstTypeKind::Integer(IntegerType::new(16, true))),
+            TokenKind::I32Type => Ok(AstTypeKind::Integer(IntegerType::new(32, true))),
+            TokenKind::I64Type => Ok(AstTypeKind::Int),
+            TokenKind::U8Type => Ok(AstTypeKind::Integer(IntegerType::new(8, false))),
+            TokenKind::U16Type => Ok(AstTypeKind::Integer(IntegerType::new(16, false))),
+            TokenKind::U32Type => Ok(AstTypeKind::Integer(IntegerType::new(32, false))),
+            TokenKind::U64Type => Ok(AstTypeKind::Integer(IntegerType::new(64, false))),
+            TokenKind::CharType => Ok(AstTypeKind::Integer(IntegerType::CHAR)),
             TokenKind::FloatType => Ok(AstTypeKind::Float),
+            TokenKind::F32Type => Ok(AstTypeKind::F32),
+            TokenKind::F64Type => Ok(AstTypeKind::Float),
             TokenKind::StringType => Ok(AstTypeKind::String),
             TokenKind::BooleanType => Ok(AstTypeKind::Boolean),
             TokenKind::VoidType => Ok(AstTypeKind::Void),
-            TokenKind::Define => todo!(),
+            TokenKind::Define => {
+                self.next_token();
+
+                self.expect_token_consume(TokenKind::LParen)?;
+
+                let mut parameters = Vec::new();
+
+                while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                    parameters.push(self.parse_ty()?);
+                    self.next_token();
+
+                    if self.current_token.kind == TokenKind::RParen {
+                        break;
+                    }
+
+                    self.expect_token_consume(TokenKind::Comma)?;
+                }
+
+                self.expect_token_consume(TokenKind::RParen)?;
+                self.expect_token_consume(TokenKind::Arrow)?;
+
+                Ok(AstTypeKind::Function(
+                    parameters,
+                    Box::new(self.parse_ty()?),
+                ))
+            }
             TokenKind::At => {
                 self.next_token();
 
                 Ok(AstTypeKind::TypeAlias(identifier! { self }))
             }
+            TokenKind::LParen => {
+                self.next_token();
+
+                let mut parameters = Vec::new();
+
+                while self.current_token.kind != TokenKind::RParen && !self.is_eof() {
+                    parameters.push(self.parse_ty()?);
+                    self.next_token();
+
+                    if self.current_token.kind == TokenKind::RParen {
+                        break;
+                    }
+
+                    self.expect_token_consume(TokenKind::Comma)?;
+                }
+
+                self.expect_token(TokenKind::RParen)?;
+
+                // Without a return type, the parenthesized types form a tuple.
+                if self.peek_token.kind != TokenKind::Arrow {
+                    Ok(AstTypeKind::Tuple(parameters))
+                } else {
+                    self.next_token();
+                    self.next_token();
+
+                    Ok(AstTypeKind::Closure(parameters, Box::new(self.parse_ty()?)))
+                }
+            }
+            TokenKind::Identifier(identifier) if has_type_arguments => {
+                let name = Identifier {
+                    identifier: identifier.to_string(),
+                    span: self.span,
+                };
+                self.next_token();
+                self.next_token();
+
+                let mut arguments = Vec::new();
+
+                while self.current_token.kind != TokenKind::GT && !self.is_eof() {
+                    arguments.push(self.parse_ty()?);
+                    self.next_token();
+                    self.split_right_shift();
+
+                    if self.current_token.kind == TokenKind::GT {
+                        break;
+                    }
+
+                    self.expect_token_consume(TokenKind::Comma)?;
+                }
+
+                self.expect_token(TokenKind::GT)?;
+
+                Ok(AstTypeKind::Generic(name, arguments))
+            }
             TokenKind::Identifier(identifier) => Ok(AstTypeKind::Struct(Identifier {
                 identifier: identifier.to_string(),
                 span: self.span,
@@ -950,7 +1932,11 @@ where
                 self.current_token.kind.to_string(),
                 self.span,
             )),
-        };
+        }
+    }
+
+    fn parse_ty(&mut self) -> ParseResult<AstType> {
+        let mut ty = self.parse_base_ty();
 
         if self.peek_token.kind == TokenKind::LBracket {
             self.next_token();
@@ -1003,3 +1989,117 @@ where
         })
     }
 }
+
+// Whether the last token of `expression` may be the dedent closing an indented block.
+fn ends_with_block(expression: &Expression) -> bool {
+    match expression {
+        Expression::Match(_) | Expression::Lambda(_) => true,
+        Expression::Assign(assign) => ends_with_block(&assign.value),
+        Expression::Binary(binary) => ends_with_block(&binary.right),
+        Expression::Unary(unary) => ends_with_block(&unary.expression),
+        Expression::Ternary(ternary) => ends_with_block(&ternary.alternative),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::{
+        codegen::{types::AstTypeKind, Expression, Literal, NewExpressionKind, Statement},
+        lexer::Lexer,
+        preprocessor::Preprocessor,
+        BinaryOperator,
+    };
+
+    // The value returned by the last function of `source`.
+    fn returned(source: &str) -> Expression {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.tokenize().unwrap();
+        let tokens = Preprocessor::new(lexer.tokens.into_iter())
+            .preprocess()
+            .unwrap();
+        let program = Parser::new(tokens.into_iter()).parse_program().unwrap();
+
+        let Some(Statement::Function(function)) = program.statements.last() else {
+            panic!("no function in `{source}`");
+        };
+        let Some(Statement::Return(statement)) = function.body.statements.last() else {
+            panic!("no return in `{source}`");
+        };
+        statement.value.clone()
+    }
+
+    #[test]
+    fn lambda_parameters() {
+        for source in [
+            "define f -> () -> int = () -> int => 1",
+            "define f -> (int) -> int = (x int) -> int => x",
+            "define f -> ((int) -> int) -> int = (g (int) -> int) -> int => g(1)",
+            "define f -> ((int, int)) -> int = (pair (int, int)) -> int => pair.0",
+        ] {
+            assert!(
+                matches!(returned(source), Expression::Lambda(_)),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn parenthesized_calls() {
+        assert!(matches!(
+            returned("define f(g define(int) -> int) -> int = (g (1))"),
+            Expression::Call(_)
+        ));
+        assert!(matches!(
+            returned("define f(x int) -> int = (x)"),
+            Expression::Literal(Literal::Identifier(_))
+        ));
+
+        // Telling whether a parenthesis starts a lambda only scans ahead to the one closing it,
+        // so deeply nested calls are not parsed over and over.
+        let depth = 24;
+        let source = format!(
+            "define f(g define(int) -> int) -> int = {}1{}",
+            "(g ".repeat(depth),
+            ")".repeat(depth)
+        );
+        assert!(matches!(returned(&source), Expression::Call(_)));
+    }
+
+    #[test]
+    fn len_is_a_call() {
+        let Expression::Call(call) = returned("define f(x int[]) -> int = len(x)") else {
+            panic!("`len(x)` is not a call");
+        };
+        assert!(matches!(
+            *call.function,
+            Expression::Literal(Literal::Identifier(ref identifier)) if identifier.identifier == "len"
+        ));
+    }
+
+    #[test]
+    fn comparison_after_cast() {
+        let Expression::Binary(binary) = returned("define f<T>(x int, y T) -> bool = x as T < y")
+        else {
+            panic!("`x as T < y` is not a comparison");
+        };
+        assert!(matches!(binary.operator, BinaryOperator::LT));
+        assert!(matches!(*binary.left, Expression::Cast(_)));
+    }
+
+    #[test]
+    fn nested_type_arguments() {
+        let Expression::New(new) = returned("define f -> Box<Box<int>>* = new Box<Box<int>>")
+        else {
+            panic!("not a new expression");
+        };
+        let NewExpressionKind::Type(ty) = new.kind else {
+            panic!("`new` of a value");
+        };
+        let AstTypeKind::Generic(_, arguments) = ty.kind else {
+            panic!("not a generic type");
+        };
+        assert!(matches!(arguments[0].kind, AstTypeKind::Generic(..)));
+    }
+}
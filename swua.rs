@@ -1,4 +1,13 @@
-use std::ffi::{CStr, CString};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+};
+
+extern "C" {
+    fn calloc(count: usize, size: usize) -> *mut u8;
+    fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
+    fn free(ptr: *mut u8);
+}
 
 #[no_mangle]
 pub extern "C" fn print(x: i64) -> i64 {
@@ -18,8 +27,20 @@ pub extern "C" fn print_str(x: *const i8) -> *const i8 {
     let str_slice: &str = c_str.to_str().unwrap();
     println!("{str_slice}");
 
-    let out_str = CString::new(format!("Hello {} from Rust!", str_slice)).unwrap();
-    out_str.into_raw()
+    return_str(format!("Hello {} from Rust!", str_slice))
+}
+
+thread_local! {
+    static RETURNED_STR: RefCell<CString> = RefCell::default();
+}
+
+// Strings returned by external functions are C strings, which the compiler copies into new
+// strings right away, so the same buffer is reused by every call.
+fn return_str(string: String) -> *const i8 {
+    RETURNED_STR.with(|returned| {
+        *returned.borrow_mut() = CString::new(string).unwrap();
+        returned.borrow().as_ptr()
+    })
 }
 
 #[no_mangle]
@@ -32,8 +53,7 @@ pub extern "C" fn print_array(x: *const i64, len: i64) -> i64 {
 
 #[no_mangle]
 pub extern "C" fn to_str(x: i64) -> *const i8 {
-    let out_str = CString::new(format!("{x}")).unwrap();
-    out_str.into_raw()
+    return_str(format!("{x}"))
 }
 
 #[no_mangle]
@@ -44,6 +64,294 @@ pub extern "C" fn concat_str(x: *const i8, y: *const i8) -> *const i8 {
     let y_str = unsafe { CStr::from_ptr(y) };
     let y_slice: &str = y_str.to_str().unwrap();
 
-    let out_str = CString::new(format!("{x_slice}{y_slice}")).unwrap();
-    out_str.into_raw()
+    return_str(format!("{x_slice}{y_slice}"))
+}
+
+#[no_mangle]
+pub extern "C" fn sort_array(
+    x: *mut i64,
+    len: i64,
+    compare: extern "C" fn(i64, i64) -> i64,
+) -> i64 {
+    let slice = unsafe { std::slice::from_raw_parts_mut(x, len as usize) };
+    slice.sort_by(|a, b| compare(*a, *b).cmp(&0));
+
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn panic_index_out_of_bounds(
+    index: i64,
+    len: i64,
+    file: *const i8,
+    line: i64,
+    column: i64,
+) -> ! {
+    let file = unsafe { CStr::from_ptr(file) }.to_str().unwrap();
+    eprintln!(
+        "index out of bounds: the len is {len} but the index is {index} ({file}:{line}:{column})"
+    );
+
+    std::process::exit(101)
+}
+
+#[no_mangle]
+pub extern "C" fn panic_slice_out_of_range(
+    start: i64,
+    end: i64,
+    len: i64,
+    file: *const i8,
+    line: i64,
+    column: i64,
+) -> ! {
+    let file = unsafe { CStr::from_ptr(file) }.to_str().unwrap();
+    eprintln!(
+        "slice out of range: the len is {len} but the range is {start}..{end} ({file}:{line}:{column})"
+    );
+
+    std::process::exit(101)
+}
+
+#[no_mangle]
+pub extern "C" fn panic_negative_length(len: i64, file: *const i8, line: i64, column: i64) -> ! {
+    let file = unsafe { CStr::from_ptr(file) }.to_str().unwrap();
+    eprintln!("negative length: cannot allocate {len} elements ({file}:{line}:{column})");
+
+    std::process::exit(101)
+}
+
+// Memory returned by `allocate` follows this header, whose tag tells memory allocated by `new`
+// apart from any other pointer, including one that was already deleted.
+#[repr(C, align(16))]
+pub struct AllocationHeader {
+    tag: u64,
+}
+
+const ALLOCATION_TAG: u64 = 0x5357_5541_414c_4c43;
+
+#[no_mangle]
+pub extern "C" fn allocate(size: i64) -> *mut u8 {
+    let total = std::mem::size_of::<AllocationHeader>() + size as usize;
+
+    let header = unsafe { calloc(1, total) } as *mut AllocationHeader;
+    if header.is_null() {
+        eprintln!("out of memory: failed to allocate {size} bytes");
+        std::process::exit(101);
+    }
+
+    unsafe {
+        header.write(AllocationHeader {
+            tag: ALLOCATION_TAG,
+        });
+        header.add(1) as *mut u8
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn deallocate(ptr: *mut u8) {
+    // Memory allocated by `new` is aligned like its header, which a slice within it may not be.
+    let header = (ptr as *mut AllocationHeader).wrapping_sub(1);
+    if ptr.is_null() || !header.is_aligned() || unsafe { (*header).tag } != ALLOCATION_TAG {
+        eprintln!("invalid delete: the value was not allocated by `new` or was already deleted");
+        std::process::exit(101);
+    }
+
+    // Cleared so that deleting the memory again is detected.
+    unsafe {
+        (*header).tag = 0;
+        free(header as *mut u8)
+    }
+}
+
+// The compiler reads `data` and `len` directly, so the layout of the header must not change.
+#[repr(C)]
+pub struct VecHeader {
+    data: *mut u8,
+    len: i64,
+    capacity: i64,
+    element_size: i64,
+}
+
+#[no_mangle]
+pub extern "C" fn vec_new(element_size: i64) -> *mut VecHeader {
+    Box::into_raw(Box::new(VecHeader {
+        data: std::ptr::null_mut(),
+        len: 0,
+        capacity: 0,
+        element_size,
+    }))
+}
+
+#[no_mangle]
+pub extern "C" fn vec_push(vec: *mut VecHeader) -> *mut u8 {
+    let vec = unsafe { &mut *vec };
+
+    if vec.len == vec.capacity {
+        let capacity = if vec.capacity == 0 {
+            4
+        } else {
+            vec.capacity * 2
+        };
+        let size = capacity * vec.element_size;
+
+        let data = unsafe { realloc(vec.data, size as usize) };
+        if data.is_null() {
+            eprintln!("out of memory: failed to allocate {size} bytes");
+            std::process::exit(101);
+        }
+
+        vec.data = data;
+        vec.capacity = capacity;
+    }
+
+    vec.len += 1;
+    unsafe { vec.data.add(((vec.len - 1) * vec.element_size) as usize) }
+}
+
+#[no_mangle]
+pub extern "C" fn vec_pop(vec: *mut VecHeader) -> *mut u8 {
+    let vec = unsafe { &mut *vec };
+
+    if vec.len == 0 {
+        eprintln!("cannot pop from an empty vec");
+        std::process::exit(101);
+    }
+
+    // The popped element stays in place until the next push overwrites it.
+    vec.len -= 1;
+    unsafe { vec.data.add((vec.len * vec.element_size) as usize) }
+}
+
+#[no_mangle]
+pub extern "C" fn vec_free(vec: *mut VecHeader) {
+    let vec = unsafe { Box::from_raw(vec) };
+    unsafe { free(vec.data) }
+}
+
+// A string points to its null-terminated bytes, which follow this header. The compiler reads the
+// length directly, so the layout must not change. Literals are never freed, which is marked by a
+// negative reference count.
+#[repr(C)]
+pub struct StrHeader {
+    refcount: i64,
+    len: i64,
+}
+
+// New strings are owned by the caller, so they start with a single reference.
+fn new_str(bytes: &[u8]) -> *const i8 {
+    let size = std::mem::size_of::<StrHeader>() + bytes.len() + 1;
+
+    let header = unsafe { calloc(1, size) } as *mut StrHeader;
+    if header.is_null() {
+        eprintln!("out of memory: failed to allocate {size} bytes");
+        std::process::exit(101);
+    }
+
+    unsafe {
+        header.write(StrHeader {
+            refcount: 1,
+            len: bytes.len() as i64,
+        });
+
+        let data = header.add(1) as *mut u8;
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+
+        data as *const i8
+    }
+}
+
+fn str_header(string: *const i8) -> *mut StrHeader {
+    unsafe { (string as *mut StrHeader).sub(1) }
+}
+
+#[no_mangle]
+pub extern "C" fn str_retain(string: *const i8) {
+    if string.is_null() {
+        return;
+    }
+
+    let header = unsafe { &mut *str_header(string) };
+    if header.refcount >= 0 {
+        header.refcount += 1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn str_release(string: *const i8) {
+    if string.is_null() {
+        return;
+    }
+
+    let header = unsafe { &mut *str_header(string) };
+    if header.refcount > 0 {
+        header.refcount -= 1;
+        if header.refcount == 0 {
+            unsafe { free(header as *mut StrHeader as *mut u8) }
+        }
+    }
+}
+
+// A null pointer, such as the one returned by `getenv` for a missing variable, becomes an empty
+// string.
+#[no_mangle]
+pub extern "C" fn str_from_cstr(ptr: *const i8) -> *const i8 {
+    if ptr.is_null() {
+        return new_str(&[]);
+    }
+
+    new_str(unsafe { CStr::from_ptr(ptr) }.to_bytes())
+}
+
+// A closure environment follows this header. Unlike a string, it holds references of its own,
+// which are released by `drop` before it is freed.
+#[repr(C)]
+pub struct ClosureHeader {
+    refcount: i64,
+    drop: Option<extern "C" fn(*mut u8)>,
+}
+
+#[no_mangle]
+pub extern "C" fn closure_new(size: i64, drop: Option<extern "C" fn(*mut u8)>) -> *mut u8 {
+    let size = std::mem::size_of::<ClosureHeader>() + size as usize;
+
+    let header = unsafe { calloc(1, size) } as *mut ClosureHeader;
+    if header.is_null() {
+        eprintln!("out of memory: failed to allocate {size} bytes");
+        std::process::exit(101);
+    }
+
+    unsafe {
+        header.write(ClosureHeader { refcount: 1, drop });
+        header.add(1) as *mut u8
+    }
+}
+
+fn closure_header(environment: *mut u8) -> *mut ClosureHeader {
+    unsafe { (environment as *mut ClosureHeader).sub(1) }
+}
+
+// Closures that capture nothing have no environment.
+#[no_mangle]
+pub extern "C" fn closure_retain(environment: *mut u8) {
+    if environment.is_null() {
+        return;
+    }
+
+    unsafe { (*closure_header(environment)).refcount += 1 }
+}
+
+#[no_mangle]
+pub extern "C" fn closure_release(environment: *mut u8) {
+    if environment.is_null() {
+        return;
+    }
+
+    let header = unsafe { &mut *closure_header(environment) };
+    header.refcount -= 1;
+    if header.refcount == 0 {
+        if let Some(drop) = header.drop {
+            drop(environment);
+        }
+        unsafe { free(header as *mut ClosureHeader as *mut u8) }
+    }
 }